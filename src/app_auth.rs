@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Context, Error};
+use chrono::{DateTime, TimeDelta, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, sync::Mutex};
+use tracing::{debug, info};
+
+use crate::{config::AppConfig, error::ApiError, request::http_message};
+
+// installation tokens last an hour, get a new one well before that so a slow request can't outlive it
+const REFRESH_BEFORE: TimeDelta = TimeDelta::minutes(5);
+
+/// Authenticates as a GitHub App installation. The app's JWT is exchanged for an installation token,
+/// which is handed out to every GH request and replaced shortly before it expires.
+pub struct AppAuth {
+    app_id: u64,
+    key: EncodingKey,
+    access_tokens_url: String,
+    // without an Authorization header, the JWT is added per request
+    client: Client,
+    token: Mutex<Option<InstallationToken>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+impl AppAuth {
+    /// `rest_url` is the REST API root for the GH the app is installed on, i.e.
+    /// `https://api.github.com` or `https://<host>/api/v3`.
+    pub fn new(config: &AppConfig, rest_url: &str, client: Client) -> Result<AppAuth, Error> {
+        let pem = match &config.private_key_path {
+            Some(path) => fs::read(path).with_context(|| {
+                format!("Couldn't read GitHub App private key {}.", path.display())
+            })?,
+            None => env::var("GITHUB_APP_PRIVATE_KEY")
+                .map_err(|_| {
+                    anyhow!("Missing GITHUB_APP_PRIVATE_KEY, or github.app.private_key_path.")
+                })?
+                .into_bytes(),
+        };
+        let key = EncodingKey::from_rsa_pem(&pem)
+            .context("GitHub App private key isn't an RSA key in PEM format.")?;
+        Ok(AppAuth {
+            app_id: config.app_id,
+            key,
+            access_tokens_url: format!(
+                "{}/app/installations/{}/access_tokens",
+                rest_url.trim_end_matches('/'),
+                config.installation_id
+            ),
+            client,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// The current installation token, fetching a new one first if it's missing or about to expire.
+    pub fn token(&self) -> Result<String, ApiError> {
+        let mut current = self.token.lock().unwrap_or_else(|e| e.into_inner());
+        match &*current {
+            Some(token) if token.expires_at - Utc::now() > REFRESH_BEFORE => {}
+            _ => {
+                let token = self.fetch_token()?;
+                info!(expires_at = %token.expires_at, "Got a GitHub App installation token");
+                *current = Some(token);
+            }
+        }
+        Ok(current
+            .as_ref()
+            .map(|t| t.token.clone())
+            .unwrap_or_default())
+    }
+
+    fn fetch_token(&self) -> Result<InstallationToken, ApiError> {
+        const OPERATION: &str = "CreateInstallationAccessToken";
+        debug!(url = %self.access_tokens_url, "Exchanging the GitHub App JWT for an installation token");
+        let response = self
+            .client
+            .post(&self.access_tokens_url)
+            .bearer_auth(self.jwt()?)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .map_err(|e| ApiError::transport(OPERATION, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let messages = vec![http_message(status, response)];
+            let operation = OPERATION.to_string();
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiError::PermissionDenied {
+                    operation,
+                    messages,
+                },
+                StatusCode::NOT_FOUND => ApiError::NotFound {
+                    operation,
+                    messages,
+                },
+                _ => ApiError::Transport {
+                    operation,
+                    messages,
+                },
+            });
+        }
+        response
+            .json()
+            .map_err(|e| ApiError::transport(OPERATION, e))
+    }
+
+    // GH only accepts app JWTs that expire within 10 minutes, and issued a minute early for clock skew
+    fn jwt(&self) -> Result<String, ApiError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            iat: now - 60,
+            exp: now + 9 * 60,
+            iss: self.app_id.to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(|e| ApiError::transport("CreateInstallationAccessToken", e))
+    }
+}
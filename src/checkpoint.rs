@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Context, Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::sync::IssueKey;
+
+/// Progress of a sync, written after every issue so a run that dies partway through can be resumed
+/// with `sync --resume` instead of starting over.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    // a checkpoint only makes sense for the workspace and project it was written for
+    pub workspace_id: String,
+    pub project_id: String,
+    /// Every issue that's been dealt with, in ZH order, along with their GH project item IDs. That
+    /// includes the ones that didn't need any changes, so a resumed run still knows the whole order.
+    pub synced: Vec<SyncedIssue>,
+    /// Where to pick the ZH reads back up. `None` reads every pipeline from the start, skipping the
+    /// synced issues.
+    pub resume_from: Option<ResumePoint>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedIssue {
+    pub issue: IssueKey,
+    // missing from older checkpoints
+    #[serde(default)]
+    pub pipeline: String,
+    pub item_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumePoint {
+    pub pipeline_id: String,
+    // cursor for the page of the pipeline to start from, `None` for the first page
+    pub cursor: Option<String>,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path, workspace_id: &str, project_id: &str) -> Checkpoint {
+        Checkpoint {
+            workspace_id: workspace_id.to_string(),
+            project_id: project_id.to_string(),
+            synced: vec![],
+            resume_from: None,
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn load(path: &Path, workspace_id: &str, project_id: &str) -> Result<Checkpoint, Error> {
+        let contents = fs::read_to_string(path).with_context(|| {
+            format!(
+                "Couldn't read checkpoint file {} to resume from.",
+                path.display()
+            )
+        })?;
+        let mut checkpoint: Checkpoint = serde_json::from_str(&contents)
+            .with_context(|| format!("Couldn't parse checkpoint file {}.", path.display()))?;
+        if checkpoint.workspace_id != workspace_id || checkpoint.project_id != project_id {
+            return Err(anyhow!(
+                "Checkpoint file {} is for a different workspace or project than the config.",
+                path.display()
+            ));
+        }
+        checkpoint.path = path.to_path_buf();
+        Ok(checkpoint)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn synced_issues(&self) -> HashSet<&IssueKey> {
+        self.synced.iter().map(|s| &s.issue).collect()
+    }
+
+    /// Record issues as done and save straight away.
+    pub fn record(
+        &mut self,
+        synced: Vec<SyncedIssue>,
+        resume_from: Option<ResumePoint>,
+    ) -> Result<(), Error> {
+        self.synced.extend(synced);
+        self.resume_from = resume_from;
+        self.save()
+    }
+
+    // written to a temp file then renamed into place, so dying mid-write can't corrupt the checkpoint
+    fn save(&self) -> Result<(), Error> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Couldn't write checkpoint file {}.", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Couldn't write checkpoint file {}.", self.path.display()))?;
+        Ok(())
+    }
+
+    /// The sync finished without any failures, so there's nothing left to resume.
+    pub fn finish(self) -> Result<(), Error> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| {
+                format!("Couldn't remove checkpoint file {}.", self.path.display())
+            }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about = "Sync Zenhub Workspace issues to a GitHub Project.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+#[derive(Args)]
+pub struct GlobalArgs {
+    /// Path to the TOML config file with the project, workspace, and field mappings.
+    #[arg(short, long, global = true, default_value = "config.toml")]
+    pub config: PathBuf,
+    /// Show more logs. Pass once for debug logs, twice to include raw API requests and responses.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Only show command output, warnings, and errors, no progress logs.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Format of the command output on stdout.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Format of the logs on stderr. JSON logs have one object per line, for CI.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub log_format: OutputFormat,
+    /// Stop at the first issue that fails instead of carrying on and reporting every failure at the end.
+    #[arg(long, global = true)]
+    pub fail_fast: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Sync the Zenhub workspace to the GitHub project.
+    Sync {
+        /// Pick up where a failed sync left off, using its checkpoint file.
+        #[arg(long)]
+        resume: bool,
+        /// Where progress is checkpointed during a sync. It's removed once a sync finishes without any failures.
+        #[arg(long, default_value = ".zenhub-sync-checkpoint.json")]
+        checkpoint: PathBuf,
+        /// Where to append the journal of every mutation made. Defaults to a new timestamped file.
+        #[arg(long)]
+        journal: Option<PathBuf>,
+        /// Write a report with the outcome of every issue, as CSV if the path ends in .csv or JSON otherwise.
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Create the mapped GH fields that are missing, and add missing options to the single select ones, once preflight passes.
+        #[arg(long)]
+        create_fields: bool,
+    },
+    /// Do all the reads and print the changes a sync would make, without making them.
+    Plan {
+        /// Write a report with the planned outcome of every issue, as CSV if the path ends in .csv or JSON otherwise.
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Show what's available on either side, useful when writing a config.
+    Inspect {
+        #[command(subcommand)]
+        target: InspectTarget,
+    },
+    /// Check the config against the live Zenhub workspace and GitHub project.
+    Validate,
+    /// Audit the GitHub project against the Zenhub workspace after a sync, exiting non-zero on any difference.
+    Verify,
+    /// Undo the mutations recorded in a sync's journal, newest first.
+    Rollback {
+        /// The journal file a sync wrote.
+        journal: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum InspectTarget {
+    /// List the Zenhub workspaces in the token's organizations, and their pipelines.
+    Workspace,
+    /// List the fields and options of the configured GitHub project.
+    Project,
+}
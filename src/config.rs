@@ -0,0 +1,718 @@
+use anyhow::{anyhow, Context, Error};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Zenhub field names we know how to carry over to GitHub.
+pub const SUPPORTED_ZH_FIELDS: [&str; 5] =
+    ["Estimate", "Priority", "Pipeline", "Sprint", "Blocking"];
+/// Zenhub fields that every config has to map, the rest are only synced if they're mapped.
+pub const REQUIRED_ZH_FIELDS: [&str; 3] = ["Estimate", "Priority", "Pipeline"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub github: GithubConfig,
+    pub zenhub: ZenhubConfig,
+    /// ZH field name -> GH field name
+    pub fields: HashMap<String, String>,
+    /// ZH pipeline name -> GH status option name
+    pub lanes: HashMap<String, String>,
+    #[serde(default)]
+    pub priorities: PriorityConfig,
+    #[serde(default)]
+    pub sprints: SprintConfig,
+    #[serde(default)]
+    pub epics: EpicConfig,
+    #[serde(default)]
+    pub blocking: BlockingConfig,
+    #[serde(default)]
+    pub closed: ClosedConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GithubConfig {
+    /// No longer needed since the owner comes from `project_url`, it has to match when it's set.
+    pub organization: Option<String>,
+    /// i.e. `https://github.com/orgs/<org>/projects/<n>` or `https://github.com/users/<user>/projects/<n>`
+    pub project_url: String,
+    /// GraphQL endpoint. Defaults to api.github.com for github.com projects, and the GHES endpoint on
+    /// the project's host otherwise.
+    pub api_url: Option<String>,
+    /// Authenticate as a GitHub App installation instead of with `GITHUB_TOKEN`.
+    pub app: Option<AppConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    pub app_id: u64,
+    pub installation_id: u64,
+    /// PEM private key file for the app. The key is read from `GITHUB_APP_PRIVATE_KEY` without it.
+    pub private_key_path: Option<PathBuf>,
+}
+
+/// Whether a GH project belongs to an organization or a user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OwnerKind {
+    Organization,
+    User,
+}
+
+/// The owner and number a GH project is looked up by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectRef {
+    pub owner_kind: OwnerKind,
+    pub owner: String,
+    pub number: i64,
+}
+
+impl GithubConfig {
+    /// Project URLs look like `https://<host>/orgs/<owner>/projects/<n>` or `/users/<owner>/...`,
+    /// optionally followed by the `/views/<n>` of a particular view.
+    pub fn project(&self) -> Result<ProjectRef, Error> {
+        let url = Url::parse(&self.project_url)
+            .map_err(|e| anyhow!("Provided project URL isn't a valid URL: {e}"))?;
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        match segments.as_slice() {
+            [kind @ ("orgs" | "users"), owner, "projects", number]
+            | [kind @ ("orgs" | "users"), owner, "projects", number, "views", _] => {
+                Ok(ProjectRef {
+                    owner_kind: match *kind {
+                        "orgs" => OwnerKind::Organization,
+                        _ => OwnerKind::User,
+                    },
+                    owner: owner.to_string(),
+                    number: number
+                        .parse()
+                        .map_err(|_| anyhow!("Provided project URL's project number isn't a number."))?,
+                })
+            }
+            _ => Err(anyhow!(
+                "Provided project URL isn't an /orgs/<org>/projects/<n> or /users/<user>/projects/<n> URL."
+            )),
+        }
+    }
+
+    pub fn api_url(&self) -> Result<String, Error> {
+        if let Some(api_url) = &self.api_url {
+            return Ok(api_url.clone());
+        }
+        let url = Url::parse(&self.project_url)
+            .map_err(|e| anyhow!("Provided project URL isn't a valid URL: {e}"))?;
+        match url.host_str() {
+            Some("github.com") | Some("www.github.com") => {
+                Ok("https://api.github.com/graphql".to_string())
+            }
+            Some(host) => Ok(match url.port() {
+                Some(port) => format!("{}://{host}:{port}/api/graphql", url.scheme()),
+                None => format!("{}://{host}/api/graphql", url.scheme()),
+            }),
+            None => Err(anyhow!("Provided project URL has no host.")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZenhubConfig {
+    /// The workspace's ID, its app URL, or its name.
+    pub workspace: String,
+    /// GraphQL endpoint, for Zenhub Enterprise on-prem. Defaults to the Zenhub cloud endpoint.
+    pub api_url: Option<String>,
+}
+
+/// How both clients connect, for networks that go through a proxy or intercept TLS.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy URL for all requests, i.e. `http://proxy.example.com:3128`. Without it the usual
+    /// `HTTPS_PROXY` and friends are used.
+    pub proxy: Option<String>,
+    /// PEM files of extra CA certificates to trust, on top of the system ones.
+    #[serde(default)]
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+/// Just the parts of the config that clients are built from. Commands that don't otherwise need a
+/// config still read these when there is one, without requiring the rest to be filled in.
+#[derive(Debug, Default, Deserialize)]
+pub struct Connection {
+    #[serde(default)]
+    pub github: GithubConnection,
+    #[serde(default)]
+    pub zenhub: ZenhubConnection,
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GithubConnection {
+    pub app: Option<AppConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ZenhubConnection {
+    pub api_url: Option<String>,
+}
+
+impl Connection {
+    pub fn load(path: &Path) -> Result<Connection, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Connection::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Couldn't read config file {}.", path.display()))
+            }
+        };
+        toml::from_str(&contents)
+            .with_context(|| format!("Couldn't parse config file {}.", path.display()))
+    }
+}
+
+/// How the config picks the ZH workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceRef {
+    Id(String),
+    Name(String),
+}
+
+impl ZenhubConfig {
+    /// App URLs look like `https://app.zenhub.com/workspaces/<slug>-<id>/board`, with the ID on the
+    /// end of the path segment after `workspaces`. Anything that isn't an ID or a URL is a name.
+    pub fn workspace_ref(&self) -> Result<WorkspaceRef, Error> {
+        let workspace = self.workspace.trim();
+        if is_workspace_id(workspace) {
+            return Ok(WorkspaceRef::Id(workspace.to_string()));
+        }
+        if workspace.starts_with("https://") || workspace.starts_with("http://") {
+            return workspace
+                .split("/workspaces/")
+                .nth(1)
+                .and_then(|path| path.split('/').next())
+                .and_then(|segment| segment.rsplit('-').next())
+                .filter(|id| is_workspace_id(id))
+                .map(|id| WorkspaceRef::Id(id.to_string()))
+                .ok_or_else(|| anyhow!("Provided workspace URL didn't contain a workspace ID."));
+        }
+        Ok(WorkspaceRef::Name(self.workspace.clone()))
+    }
+}
+
+// ZH workspace IDs are 24 hex digits
+fn is_workspace_id(s: &str) -> bool {
+    s.len() == 24 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriorityConfig {
+    /// GH option for issues that don't have a ZH priority. They're left unset if this is missing.
+    pub default: Option<String>,
+    /// ZH priority name or ID -> GH priority option name. Any number of ZH priorities can map to the
+    /// same GH option.
+    #[serde(default)]
+    pub map: HashMap<String, String>,
+}
+
+impl PriorityConfig {
+    /// The GH option name for a ZH priority's `(ID, name)`. IDs are checked before names, and
+    /// priorities that aren't mapped get `None`.
+    pub fn gh_option(&self, zh_priority: Option<(&str, &str)>) -> Option<&String> {
+        match zh_priority {
+            Some((id, name)) => self.map.get(id).or_else(|| self.map.get(name)),
+            None => self.default.as_ref(),
+        }
+    }
+
+    /// Every GH option name the config refers to.
+    pub fn gh_options(&self) -> impl Iterator<Item = &String> {
+        self.map.values().chain(self.default.iter())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SprintConfig {
+    #[serde(default)]
+    pub match_by: SprintMatch,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EpicConfig {
+    /// Attach each ZH epic's child issues to the epic's GH issue as sub-issues.
+    #[serde(default)]
+    pub sub_issues: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockingConfig {
+    /// Also comment on blocked issues with what's blocking them, so it's visible outside the project.
+    #[serde(default)]
+    pub comment: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClosedConfig {
+    #[serde(default)]
+    pub policy: ClosedPolicy,
+    /// GH status option name closed issues get with the `done` and `archive` policies.
+    pub status: Option<String>,
+}
+
+/// What happens to closed ZH issues, whether they're in the Closed pipeline or still in another one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClosedPolicy {
+    /// Leave them out of the sync entirely.
+    #[default]
+    Skip,
+    /// Sync them like any other issue, but with the `closed.status` status.
+    Done,
+    /// Same as `Done`, then archive the project item.
+    Archive,
+}
+
+/// How a ZH sprint is matched up with a GH iteration.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SprintMatch {
+    /// The iteration whose date range contains the middle of the sprint.
+    #[default]
+    Dates,
+    /// The iteration whose title is the sprint's name.
+    Name,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file {}.", path.display()))?;
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Couldn't parse config file {}.", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file {}.", path.display()))?;
+        Ok(config)
+    }
+
+    // everything here has to be checkable without talking to either API
+    fn validate(&self) -> Result<(), Error> {
+        let mut problems = vec![];
+        match self.github.project() {
+            Ok(project) => match &self.github.organization {
+                Some(organization) if organization != &project.owner => problems.push(format!(
+                    "github.organization is {organization}, but github.project_url belongs to {}. It can be left out.",
+                    project.owner
+                )),
+                _ => {}
+            },
+            Err(e) => problems.push(format!("github.project_url is invalid: {e}")),
+        }
+        if let Some(path) = self
+            .github
+            .app
+            .as_ref()
+            .and_then(|app| app.private_key_path.as_ref())
+        {
+            if !path.is_file() {
+                problems.push(format!(
+                    "github.app.private_key_path {} doesn't exist.",
+                    path.display()
+                ));
+            }
+        }
+        for (key, api_url) in [
+            ("github.api_url", &self.github.api_url),
+            ("zenhub.api_url", &self.zenhub.api_url),
+            ("http.proxy", &self.http.proxy),
+        ] {
+            if let Some(Err(e)) = api_url.as_deref().map(Url::parse) {
+                problems.push(format!("{key} is invalid: {e}"));
+            }
+        }
+        if self.zenhub.workspace.is_empty() {
+            problems.push("zenhub.workspace must not be empty.".to_string());
+        } else if let Err(e) = self.zenhub.workspace_ref() {
+            problems.push(format!("zenhub.workspace is invalid: {e}"));
+        }
+        for zh_field in self.fields.keys() {
+            if !SUPPORTED_ZH_FIELDS.contains(&zh_field.as_str()) {
+                problems.push(format!(
+                    "Unknown ZH field {zh_field} in [fields], supported fields are {SUPPORTED_ZH_FIELDS:?}."
+                ));
+            }
+        }
+        for zh_field in REQUIRED_ZH_FIELDS {
+            if !self.fields.contains_key(zh_field) {
+                problems.push(format!(
+                    "[fields] is missing a mapping for ZH field {zh_field}."
+                ));
+            }
+        }
+        if self.blocking.comment && !self.fields.contains_key("Blocking") {
+            problems.push(
+                "blocking.comment needs a GH text field mapped from Blocking in [fields]."
+                    .to_string(),
+            );
+        }
+        match (&self.closed.policy, &self.closed.status) {
+            (ClosedPolicy::Skip, _) => {}
+            (_, None) => problems.push(
+                "closed.status is needed to sync closed issues, set it to a GH status option."
+                    .to_string(),
+            ),
+            (_, Some(status)) if status.trim().is_empty() => {
+                problems.push("closed.status must not be blank.".to_string())
+            }
+            _ => {}
+        }
+        if self.lanes.is_empty() {
+            problems.push("[lanes] must map at least one ZH pipeline.".to_string());
+        }
+        for (zh_lane, gh_status) in &self.lanes {
+            if zh_lane.trim().is_empty() || gh_status.trim().is_empty() {
+                problems.push(format!(
+                    "[lanes] has a blank entry ({zh_lane:?} = {gh_status:?})."
+                ));
+            }
+        }
+        for (zh_priority, gh_priority) in &self.priorities.map {
+            if zh_priority.trim().is_empty() || gh_priority.trim().is_empty() {
+                problems.push(format!(
+                    "[priorities.map] has a blank entry ({zh_priority:?} = {gh_priority:?})."
+                ));
+            }
+        }
+        if self
+            .priorities
+            .default
+            .as_ref()
+            .is_some_and(|d| d.trim().is_empty())
+        {
+            problems.push(
+                "priorities.default is blank, leave it out to leave unprioritized issues unset."
+                    .to_string(),
+            );
+        }
+        // TOML rejects exact duplicate keys, but lane names that only differ by case or padding are
+        // almost always a copy/paste mistake
+        let mut seen_lanes: HashMap<String, &str> = HashMap::new();
+        for zh_lane in self.lanes.keys() {
+            let normalized = zh_lane.trim().to_lowercase();
+            if let Some(other) = seen_lanes.insert(normalized, zh_lane) {
+                problems.push(format!(
+                    "[lanes] has duplicate ZH pipelines {other:?} and {zh_lane:?}."
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(problems.join("\n")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "5f8a1b2c3d4e5f6a7b8c9d0e";
+
+    fn github(project_url: &str, api_url: Option<&str>) -> GithubConfig {
+        GithubConfig {
+            organization: None,
+            project_url: project_url.to_string(),
+            api_url: api_url.map(|u| u.to_string()),
+            app: None,
+        }
+    }
+
+    fn zenhub(workspace: &str) -> ZenhubConfig {
+        ZenhubConfig {
+            workspace: workspace.to_string(),
+            api_url: None,
+        }
+    }
+
+    // a valid config with `extra` appended, parsed and validated the way `Config::load` does
+    fn config(extra: &str) -> Result<Config, Error> {
+        let contents = format!(
+            r#"
+[github]
+project_url = "https://github.com/orgs/acme/projects/7"
+
+[zenhub]
+workspace = "{ID}"
+
+[fields]
+Estimate = "Estimate"
+Priority = "Priority"
+Pipeline = "Status"
+
+[lanes]
+"In Progress" = "In progress"
+{extra}
+"#
+        );
+        let config: Config = toml::from_str(&contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn config_error(extra: &str) -> String {
+        format!("{:#}", config(extra).unwrap_err())
+    }
+
+    #[test]
+    fn validate_accepts_a_minimal_config() {
+        let config = config("").unwrap();
+        assert_eq!(config.lanes["In Progress"], "In progress");
+        assert_eq!(config.closed.policy, ClosedPolicy::Skip);
+    }
+
+    #[test]
+    fn validate_rejects_unknown_keys() {
+        assert!(config_error("[bogus]\nkey = 1").contains("unknown field `bogus`"));
+        assert!(config_error("[epics]\nsubissues = true").contains("unknown field `subissues`"));
+        assert!(config_error("[http]\nproxies = []").contains("unknown field `proxies`"));
+    }
+
+    #[test]
+    fn validate_rejects_unknown_zh_fields() {
+        let mut config = config("").unwrap();
+        config
+            .fields
+            .insert("Labels".to_string(), "Labels".to_string());
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown ZH field Labels in [fields]"));
+    }
+
+    #[test]
+    fn validate_rejects_lanes_that_only_differ_by_case() {
+        let error = config_error("\"in progress \" = \"In progress\"");
+        assert!(
+            error.contains("[lanes] has duplicate ZH pipelines"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn validate_requires_the_required_fields() {
+        let mut config = config("").unwrap();
+        config.fields.remove("Priority");
+        config.fields.remove("Pipeline");
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("[fields] is missing a mapping for ZH field Priority."));
+        assert!(error.contains("[fields] is missing a mapping for ZH field Pipeline."));
+        assert!(!error.contains("ZH field Estimate"));
+        let error = toml::from_str::<Config>("[github]\n[zenhub]\nworkspace = \"Team\"")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing field `project_url`"), "{error}");
+    }
+
+    #[test]
+    fn validate_requires_a_closed_status_unless_skipping() {
+        assert!(config("[closed]\npolicy = \"skip\"").is_ok());
+        for policy in ["done", "archive"] {
+            let error = config_error(&format!("[closed]\npolicy = \"{policy}\""));
+            assert!(error.contains("closed.status is needed"), "{error}");
+        }
+        assert!(config_error("[closed]\npolicy = \"done\"\nstatus = \" \"")
+            .contains("closed.status must not be blank."));
+        assert!(config("[closed]\npolicy = \"archive\"\nstatus = \"Done\"").is_ok());
+    }
+
+    fn priorities(default: Option<&str>) -> PriorityConfig {
+        PriorityConfig {
+            default: default.map(|d| d.to_string()),
+            map: HashMap::from([
+                ("High priority".to_string(), "P1".to_string()),
+                ("Low priority".to_string(), "P3".to_string()),
+                ("priority-id-low".to_string(), "P2".to_string()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn gh_option_maps_by_id_before_name() {
+        let priorities = priorities(None);
+        assert_eq!(
+            priorities.gh_option(Some(("priority-id-high", "High priority"))),
+            Some(&"P1".to_string())
+        );
+        assert_eq!(
+            priorities.gh_option(Some(("priority-id-low", "Low priority"))),
+            Some(&"P2".to_string())
+        );
+    }
+
+    #[test]
+    fn gh_option_leaves_unmapped_priorities_unset() {
+        let with_default = priorities(Some("P4"));
+        assert_eq!(
+            with_default.gh_option(Some(("priority-id-urgent", "Urgent"))),
+            None
+        );
+        assert_eq!(with_default.gh_option(None), Some(&"P4".to_string()));
+        assert_eq!(priorities(None).gh_option(None), None);
+    }
+
+    #[test]
+    fn workspace_ref_takes_ids() {
+        assert_eq!(
+            zenhub(ID).workspace_ref().unwrap(),
+            WorkspaceRef::Id(ID.to_string())
+        );
+        assert_eq!(
+            zenhub(&format!(" {ID}\n")).workspace_ref().unwrap(),
+            WorkspaceRef::Id(ID.to_string())
+        );
+    }
+
+    #[test]
+    fn workspace_ref_takes_the_id_from_app_urls() {
+        for url in [
+            format!("https://app.zenhub.com/workspaces/platform-team-{ID}/board"),
+            format!("https://app.zenhub.com/workspaces/platform-team-{ID}"),
+            format!("https://app.zenhub.com/workspaces/{ID}/board?repos=1"),
+            format!("http://zenhub.example.com/workspaces/a-b-c-{ID}/roadmap"),
+        ] {
+            assert_eq!(
+                zenhub(&url).workspace_ref().unwrap(),
+                WorkspaceRef::Id(ID.to_string()),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn workspace_ref_rejects_urls_without_an_id() {
+        for url in [
+            "https://app.zenhub.com/workspaces/platform-team/board",
+            "https://app.zenhub.com/workspaces/platform-team-5f8a1b2c3d4e5f6a7b8c9d0/board",
+            "https://app.zenhub.com/settings",
+        ] {
+            assert!(zenhub(url).workspace_ref().is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn workspace_ref_treats_anything_else_as_a_name() {
+        for name in [
+            "Platform Team",
+            "5f8a1b2c3d4e5f6a7b8c9d0",
+            "5f8a1b2c3d4e5f6a7b8c9d0g",
+        ] {
+            assert_eq!(
+                zenhub(name).workspace_ref().unwrap(),
+                WorkspaceRef::Name(name.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn project_reads_org_and_user_projects() {
+        assert_eq!(
+            github("https://github.com/orgs/acme/projects/7", None)
+                .project()
+                .unwrap(),
+            ProjectRef {
+                owner_kind: OwnerKind::Organization,
+                owner: "acme".to_string(),
+                number: 7,
+            }
+        );
+        assert_eq!(
+            github("https://github.com/users/octocat/projects/12/", None)
+                .project()
+                .unwrap(),
+            ProjectRef {
+                owner_kind: OwnerKind::User,
+                owner: "octocat".to_string(),
+                number: 12,
+            }
+        );
+    }
+
+    #[test]
+    fn project_ignores_views() {
+        let project = github("https://ghe.example.com/orgs/acme/projects/7/views/3", None)
+            .project()
+            .unwrap();
+        assert_eq!(project.owner, "acme");
+        assert_eq!(project.number, 7);
+    }
+
+    #[test]
+    fn project_rejects_other_urls() {
+        for url in [
+            "not a url",
+            "https://github.com/acme/projects/7",
+            "https://github.com/orgs/acme/projects",
+            "https://github.com/orgs/acme/projects/seven",
+            "https://github.com/orgs/acme/projects/7/settings",
+            "https://github.com/enterprises/acme/projects/7",
+        ] {
+            assert!(github(url, None).project().is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn api_url_defaults_to_the_projects_host() {
+        for (project_url, api_url) in [
+            (
+                "https://github.com/orgs/acme/projects/7",
+                "https://api.github.com/graphql",
+            ),
+            (
+                "https://www.github.com/orgs/acme/projects/7",
+                "https://api.github.com/graphql",
+            ),
+            (
+                "https://ghe.example.com/orgs/acme/projects/7",
+                "https://ghe.example.com/api/graphql",
+            ),
+            (
+                "http://ghe.example.com:8080/users/octocat/projects/1",
+                "http://ghe.example.com:8080/api/graphql",
+            ),
+        ] {
+            assert_eq!(github(project_url, None).api_url().unwrap(), api_url);
+        }
+    }
+
+    #[test]
+    fn api_url_prefers_the_configured_one() {
+        assert_eq!(
+            github(
+                "https://ghe.example.com/orgs/acme/projects/7",
+                Some("https://api.ghe.example.com/graphql")
+            )
+            .api_url()
+            .unwrap(),
+            "https://api.ghe.example.com/graphql"
+        );
+        assert!(github("not a url", None).api_url().is_err());
+    }
+}
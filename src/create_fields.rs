@@ -0,0 +1,560 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::{
+    config::{Config, SUPPORTED_ZH_FIELDS},
+    error::ApiError,
+    github::{
+        self,
+        get_fields::{
+            GetFieldsNodeOnProjectV2FieldsNodes as ProjectField,
+            GetFieldsNodeOnProjectV2FieldsNodesOnProjectV2Field as PlainField,
+            GetFieldsNodeOnProjectV2FieldsNodesOnProjectV2SingleSelectField as SingleSelectField,
+            GetFieldsNodeOnProjectV2FieldsNodesOnProjectV2SingleSelectFieldOptions as FieldOption,
+            ProjectV2FieldType, ProjectV2SingleSelectFieldOptionColor,
+        },
+        get_project_items::GetProjectItemsNodeOnProjectV2ItemsNodesFieldValuesNodes as ItemFieldValue,
+        NewFieldType, OptionColor, SelectOption,
+    },
+    journal::{Entry, Journal, OptionValue},
+    sync::{item_field_id, set_field, FieldValue},
+    validate::find_field,
+    zenhub::Workspace,
+};
+
+/// What `sync --create-fields` changes about the project's fields.
+#[derive(Debug, Default)]
+pub struct FieldPlan {
+    pub new_fields: Vec<NewField>,
+    pub new_options: Vec<NewOptions>,
+}
+
+impl FieldPlan {
+    pub fn is_empty(&self) -> bool {
+        self.new_fields.is_empty() && self.new_options.is_empty()
+    }
+}
+
+/// A mapped GH field that doesn't exist yet, and what it'll be created with.
+#[derive(Debug, Clone)]
+pub struct NewField {
+    pub name: String,
+    pub data_type: NewFieldType,
+    pub options: Vec<SelectOption>,
+}
+
+/// Options missing from a mapped single select field that already exists.
+#[derive(Debug, Clone)]
+pub struct NewOptions {
+    pub field_id: String,
+    pub field_name: String,
+    // the options the field has now by ID, they're kept first and as they are
+    pub previous: Vec<(String, SelectOption)>,
+    pub added: Vec<SelectOption>,
+}
+
+impl NewOptions {
+    /// Every option the field will have, since GH can only replace them all at once.
+    pub fn options(&self) -> Vec<SelectOption> {
+        self.previous
+            .iter()
+            .map(|(_, o)| o.clone())
+            .chain(self.added.iter().cloned())
+            .collect()
+    }
+}
+
+/// The mapped GH fields that don't exist yet, and the mapped options missing from the single select
+/// fields that do. Options follow the ZH pipeline and priority order, and priorities keep their ZH
+/// colours.
+pub fn plan(config: &Config, workspace: &Workspace, gh_fields: &[ProjectField]) -> FieldPlan {
+    let mut plan = FieldPlan::default();
+    for zh_field in SUPPORTED_ZH_FIELDS {
+        let Some(gh_name) = config.fields.get(zh_field) else {
+            continue;
+        };
+        let (data_type, options) = match zh_field {
+            "Estimate" => (NewFieldType::Number, vec![]),
+            "Blocking" => (NewFieldType::Text, vec![]),
+            "Pipeline" => (
+                NewFieldType::SingleSelect,
+                status_options(config, workspace),
+            ),
+            "Priority" => (
+                NewFieldType::SingleSelect,
+                priority_options(config, workspace),
+            ),
+            // iteration fields can't be created through the API, validation reports it as missing
+            _ => continue,
+        };
+        match find_field(gh_fields, gh_name) {
+            None => {
+                // GH won't create a single select field without any options
+                if matches!(data_type, NewFieldType::SingleSelect) && options.is_empty() {
+                    continue;
+                }
+                plan.new_fields.push(NewField {
+                    name: gh_name.clone(),
+                    data_type,
+                    options,
+                });
+            }
+            Some(ProjectField::ProjectV2SingleSelectField(ssf)) => {
+                let added: Vec<SelectOption> = options
+                    .into_iter()
+                    .filter(|o| !ssf.options.iter().any(|existing| existing.name == o.name))
+                    .collect();
+                if added.is_empty() {
+                    continue;
+                }
+                plan.new_options.push(NewOptions {
+                    field_id: ssf.id.clone(),
+                    field_name: ssf.name.clone(),
+                    previous: ssf
+                        .options
+                        .iter()
+                        .map(|o| {
+                            (
+                                o.id.clone(),
+                                SelectOption {
+                                    name: o.name.clone(),
+                                    color: same_color(&o.color).unwrap_or(OptionColor::Gray),
+                                    description: o.description.clone(),
+                                },
+                            )
+                        })
+                        .collect(),
+                    added,
+                });
+            }
+            // the wrong type is left for validation to report
+            Some(_) => {}
+        }
+    }
+    plan
+}
+
+/// The project's fields as they'll be once the plan is carried out, so the config can be validated
+/// before anything is changed. New fields and options don't have IDs yet.
+pub fn with_new_fields(gh_fields: &[ProjectField], plan: &FieldPlan) -> Vec<ProjectField> {
+    let field_option = |o: &SelectOption| FieldOption {
+        id: String::new(),
+        name: o.name.clone(),
+        color: same_color(&o.color).unwrap_or(ProjectV2SingleSelectFieldOptionColor::GRAY),
+        description: o.description.clone(),
+    };
+    let existing = gh_fields.iter().cloned().map(|field| match field {
+        ProjectField::ProjectV2SingleSelectField(mut ssf) => {
+            if let Some(new) = plan.new_options.iter().find(|n| n.field_id == ssf.id) {
+                ssf.options.extend(new.added.iter().map(field_option));
+            }
+            ProjectField::ProjectV2SingleSelectField(ssf)
+        }
+        field => field,
+    });
+    let new = plan.new_fields.iter().map(|field| match field.data_type {
+        NewFieldType::Number | NewFieldType::Text => ProjectField::ProjectV2Field(PlainField {
+            id: String::new(),
+            name: field.name.clone(),
+            data_type: match field.data_type {
+                NewFieldType::Number => ProjectV2FieldType::NUMBER,
+                _ => ProjectV2FieldType::TEXT,
+            },
+        }),
+        NewFieldType::SingleSelect => ProjectField::ProjectV2SingleSelectField(SingleSelectField {
+            id: String::new(),
+            name: field.name.clone(),
+            options: field.options.iter().map(field_option).collect(),
+        }),
+    });
+    existing.chain(new).collect()
+}
+
+/// Create the new fields and add the new options, journaling every step so rollback can undo it.
+/// Adding options replaces all of the field's options, which gives them new IDs and clears them from
+/// every item, so each item's option is read by name first and put back afterwards.
+pub fn create(
+    github_client: Client,
+    project_id: &str,
+    plan: FieldPlan,
+    journal: &mut Journal,
+) -> Result<(), Error> {
+    for field in plan.new_fields {
+        info!(field = %field.name, options = field.options.len(), "Creating the GH field");
+        let field_id = github::create_field(
+            github_client.clone(),
+            project_id,
+            &field.name,
+            field.data_type,
+            &field.options,
+        )?;
+        journal.record(Entry::CreateField {
+            field_id,
+            field_name: field.name,
+        })?;
+    }
+    if plan.new_options.is_empty() {
+        return Ok(());
+    }
+    let items = github::get_project_items(github_client.clone(), project_id)?;
+    for field in plan.new_options {
+        let names: HashMap<&str, &str> = field
+            .previous
+            .iter()
+            .map(|(id, o)| (id.as_str(), o.name.as_str()))
+            .collect();
+        // every item's option on the field by name, archived and draft items included
+        let values: Vec<OptionValue> = items
+            .iter()
+            .filter_map(|item| {
+                let option_id = item
+                    .field_values
+                    .nodes
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .find_map(|value| match value {
+                        ItemFieldValue::ProjectV2ItemFieldSingleSelectValue(v)
+                            if item_field_id(&v.field) == field.field_id =>
+                        {
+                            v.option_id.as_deref()
+                        }
+                        _ => None,
+                    })?;
+                Some(OptionValue {
+                    item_id: item.id.clone(),
+                    option: names.get(option_id)?.to_string(),
+                })
+            })
+            .collect();
+        info!(
+            field = %field.field_name,
+            options = ?field.added.iter().map(|o| &o.name).collect::<Vec<_>>(),
+            items = values.len(),
+            "Adding options to the GH field"
+        );
+        github::update_field_options(github_client.clone(), &field.field_id, &field.options())?;
+        journal.record(Entry::ReplaceOptions {
+            field_id: field.field_id.clone(),
+            field_name: field.field_name.clone(),
+            previous: field.previous.into_iter().map(|(_, o)| o).collect(),
+            values: values.clone(),
+        })?;
+        let option_ids = option_ids(github_client.clone(), project_id, &field.field_id)?;
+        for value in values {
+            info!(item_id = %value.item_id, option = %value.option, "Putting the item's option back");
+            set_field(
+                github_client.clone(),
+                project_id,
+                &value.item_id,
+                &field.field_id,
+                FieldValue::SingleSelect(option_ids.get(&value.option).cloned()),
+            )?;
+            journal.record(Entry::RestoreOption {
+                item_id: value.item_id,
+                field_id: field.field_id.clone(),
+                field_name: field.field_name.clone(),
+                option: value.option,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// A single select field's option IDs by name, read again after its options were replaced.
+pub fn option_ids(
+    github_client: Client,
+    project_id: &str,
+    field_id: &str,
+) -> Result<HashMap<String, String>, ApiError> {
+    github::get_fields(github_client, project_id)?
+        .into_iter()
+        .find_map(|field| match field {
+            ProjectField::ProjectV2SingleSelectField(ssf) if ssf.id == field_id => {
+                Some(ssf.options.into_iter().map(|o| (o.name, o.id)).collect())
+            }
+            _ => None,
+        })
+        .ok_or_else(|| ApiError::not_found(&format!("GH field {field_id} doesn't exist.")))
+}
+
+// GH's generated colour enums and `OptionColor` all go by the same names
+fn same_color<T: Serialize, U: DeserializeOwned>(color: &T) -> Option<U> {
+    serde_json::to_value(color)
+        .and_then(serde_json::from_value)
+        .ok()
+}
+
+// GH status options in ZH pipeline order, closed issues' status last
+fn status_options(config: &Config, workspace: &Workspace) -> Vec<SelectOption> {
+    let names = workspace
+        .pipelines_connection
+        .nodes
+        .iter()
+        .filter_map(|p| config.lanes.get(&p.name))
+        .chain(config.closed.status.iter());
+    dedup_options(names.map(|name| (name, OptionColor::Gray)))
+}
+
+// GH priority options in ZH priority order with the ZH colours, the default for unprioritized
+// issues last
+fn priority_options(config: &Config, workspace: &Workspace) -> Vec<SelectOption> {
+    let names = workspace
+        .priorities_connection
+        .nodes
+        .iter()
+        .filter_map(|p| {
+            config
+                .priorities
+                .gh_option(Some((p.id.as_str(), p.name.as_str())))
+                .map(|name| (name, option_color(&p.color)))
+        })
+        .chain(
+            config
+                .priorities
+                .default
+                .iter()
+                .map(|name| (name, OptionColor::Gray)),
+        );
+    dedup_options(names)
+}
+
+// several ZH pipelines or priorities can map to one GH option, the first one decides its colour
+fn dedup_options<'a>(
+    options: impl Iterator<Item = (&'a String, OptionColor)>,
+) -> Vec<SelectOption> {
+    let mut deduped: Vec<SelectOption> = vec![];
+    for (name, color) in options {
+        if !deduped.iter().any(|o| &o.name == name) {
+            deduped.push(SelectOption {
+                name: name.clone(),
+                color,
+                description: String::new(),
+            });
+        }
+    }
+    deduped
+}
+
+// ZH colours are hex, i.e. "#e53935", GH only has eight so this picks the closest hue
+fn option_color(zh_color: &str) -> OptionColor {
+    let hex = zh_color.trim().trim_start_matches('#');
+    let Some([r, g, b]) = (hex.len() == 6)
+        .then(|| [0, 2, 4].map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(f32::from)))
+        .and_then(|rgb| match rgb {
+            [Some(r), Some(g), Some(b)] => Some([r / 255.0, g / 255.0, b / 255.0]),
+            _ => None,
+        })
+    else {
+        return OptionColor::Gray;
+    };
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    // too washed out to call it a colour
+    if max == 0.0 || delta / max < 0.2 {
+        return OptionColor::Gray;
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    match hue {
+        h if h < 15.0 => OptionColor::Red,
+        h if h < 45.0 => OptionColor::Orange,
+        h if h < 70.0 => OptionColor::Yellow,
+        h if h < 170.0 => OptionColor::Green,
+        h if h < 255.0 => OptionColor::Blue,
+        h if h < 290.0 => OptionColor::Purple,
+        h if h < 345.0 => OptionColor::Pink,
+        _ => OptionColor::Red,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zenhub::get_workspace::{
+        GetWorkspaceWorkspaceClosedPipeline as ClosedPipeline,
+        GetWorkspaceWorkspacePipelinesConnection as Pipelines,
+        GetWorkspaceWorkspacePipelinesConnectionNodes as Pipeline,
+        GetWorkspaceWorkspacePrioritiesConnection as Priorities,
+        GetWorkspaceWorkspacePrioritiesConnectionNodes as Priority,
+    };
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+[github]
+project_url = "https://github.com/orgs/acme/projects/7"
+
+[zenhub]
+workspace = "Platform"
+
+[fields]
+Estimate = "Estimate"
+Priority = "Priority"
+Pipeline = "Status"
+
+[lanes]
+Backlog = "Todo"
+"In Progress" = "In progress"
+Review = "In progress"
+
+[priorities]
+map = { "High priority" = "P1" }
+
+[closed]
+policy = "done"
+status = "Done"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn workspace() -> Workspace {
+        Workspace {
+            name: Some("Platform".to_string()),
+            id: "workspace".to_string(),
+            priorities_connection: Priorities {
+                nodes: vec![Priority {
+                    id: "priority-1".to_string(),
+                    name: "High priority".to_string(),
+                    color: "#e53935".to_string(),
+                }],
+            },
+            closed_pipeline: ClosedPipeline {
+                id: "closed".to_string(),
+                name: "Closed".to_string(),
+            },
+            pipelines_connection: Pipelines {
+                nodes: ["Backlog", "In Progress", "Review"]
+                    .into_iter()
+                    .map(|name| Pipeline {
+                        id: name.to_lowercase(),
+                        name: name.to_string(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    // Estimate exists, Status only has Todo, and Priority is missing
+    fn gh_fields() -> Vec<ProjectField> {
+        vec![
+            ProjectField::ProjectV2Field(PlainField {
+                id: "estimate".to_string(),
+                name: "Estimate".to_string(),
+                data_type: ProjectV2FieldType::NUMBER,
+            }),
+            ProjectField::ProjectV2SingleSelectField(SingleSelectField {
+                id: "status".to_string(),
+                name: "Status".to_string(),
+                options: vec![FieldOption {
+                    id: "todo".to_string(),
+                    name: "Todo".to_string(),
+                    color: ProjectV2SingleSelectFieldOptionColor::BLUE,
+                    description: "Not started".to_string(),
+                }],
+            }),
+        ]
+    }
+
+    fn names(options: &[SelectOption]) -> Vec<&str> {
+        options.iter().map(|o| o.name.as_str()).collect()
+    }
+
+    #[test]
+    fn plan_creates_missing_fields() {
+        let plan = plan(&config(), &workspace(), &gh_fields());
+        assert_eq!(plan.new_fields.len(), 1);
+        let priority = &plan.new_fields[0];
+        assert_eq!(priority.name, "Priority");
+        assert!(matches!(priority.data_type, NewFieldType::SingleSelect));
+        assert_eq!(names(&priority.options), vec!["P1"]);
+        assert_eq!(priority.options[0].color, OptionColor::Red);
+    }
+
+    #[test]
+    fn plan_adds_missing_options_after_the_existing_ones() {
+        let plan = plan(&config(), &workspace(), &gh_fields());
+        assert_eq!(plan.new_options.len(), 1);
+        let status = &plan.new_options[0];
+        assert_eq!(status.field_id, "status");
+        assert_eq!(names(&status.added), vec!["In progress", "Done"]);
+        // the existing option goes back exactly as it was
+        assert_eq!(
+            status.options()[0],
+            SelectOption {
+                name: "Todo".to_string(),
+                color: OptionColor::Blue,
+                description: "Not started".to_string(),
+            }
+        );
+        assert_eq!(
+            names(&status.options()),
+            vec!["Todo", "In progress", "Done"]
+        );
+        assert_eq!(status.previous[0].0, "todo");
+    }
+
+    #[test]
+    fn plan_leaves_complete_fields_alone() {
+        let done = with_new_fields(&gh_fields(), &plan(&config(), &workspace(), &gh_fields()));
+        assert!(plan(&config(), &workspace(), &done).is_empty());
+    }
+
+    #[test]
+    fn with_new_fields_has_the_new_fields_and_options() {
+        let fields = with_new_fields(&gh_fields(), &plan(&config(), &workspace(), &gh_fields()));
+        let Some(ProjectField::ProjectV2SingleSelectField(status)) = find_field(&fields, "Status")
+        else {
+            panic!("Status should be a single select field");
+        };
+        let options: Vec<(&str, &str)> = status
+            .options
+            .iter()
+            .map(|o| (o.id.as_str(), o.name.as_str()))
+            .collect();
+        assert_eq!(
+            options,
+            vec![("todo", "Todo"), ("", "In progress"), ("", "Done")]
+        );
+        assert!(matches!(
+            find_field(&fields, "Priority"),
+            Some(ProjectField::ProjectV2SingleSelectField(f)) if f.id.is_empty() && f.options.len() == 1
+        ));
+        assert_eq!(fields.len(), 3);
+    }
+
+    #[test]
+    fn option_color_picks_the_nearest_hue() {
+        for (zh_color, color) in [
+            ("#e53935", OptionColor::Red),
+            ("#FB8C00", OptionColor::Orange),
+            ("#fdd835", OptionColor::Yellow),
+            ("#43a047", OptionColor::Green),
+            ("#1e88e5", OptionColor::Blue),
+            ("#8e24aa", OptionColor::Purple),
+            ("#d81b60", OptionColor::Pink),
+            (" 7cb342 ", OptionColor::Green),
+        ] {
+            assert_eq!(option_color(zh_color), color, "{zh_color}");
+        }
+    }
+
+    #[test]
+    fn option_color_falls_back_to_gray() {
+        for zh_color in [
+            "#9e9e9e", "#000000", "#ffffff", "#fff", "red", "", "#gg0000",
+        ] {
+            assert_eq!(option_color(zh_color), OptionColor::Gray, "{zh_color}");
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// Why a GH or ZH API call failed. Every variant carries the GraphQL operation that failed (empty when
+/// it isn't known) and the messages the server sent back, so they make it to the user intact.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The thing asked for doesn't exist, or the token can't see it.
+    NotFound {
+        operation: String,
+        messages: Vec<String>,
+    },
+    /// The token is missing a scope or the user lacks access.
+    PermissionDenied {
+        operation: String,
+        messages: Vec<String>,
+    },
+    /// Still rate limited after waiting and retrying.
+    RateLimited {
+        operation: String,
+        messages: Vec<String>,
+    },
+    /// The server rejected the request, i.e. a bad argument or an unknown field.
+    Validation {
+        operation: String,
+        messages: Vec<String>,
+    },
+    /// The request never got a usable answer: connection failures, timeouts, 5xx responses, and
+    /// bodies that couldn't be parsed or came back without the data they should have had.
+    Transport {
+        operation: String,
+        messages: Vec<String>,
+    },
+}
+
+impl ApiError {
+    /// A lookup whose answer says the thing doesn't exist, or the token can't see it.
+    pub fn not_found(message: &str) -> ApiError {
+        ApiError::NotFound {
+            operation: String::new(),
+            messages: vec![message.to_string()],
+        }
+    }
+
+    /// A response that came back without part of the data it should have had. That's never taken
+    /// to mean the thing is gone, i.e. by rollback.
+    pub fn missing(message: &str) -> ApiError {
+        ApiError::Transport {
+            operation: String::new(),
+            messages: vec![message.to_string()],
+        }
+    }
+
+    pub fn transport(operation: &str, message: impl fmt::Display) -> ApiError {
+        ApiError::Transport {
+            operation: operation.to_string(),
+            messages: vec![message.to_string()],
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ApiError::NotFound { .. })
+    }
+
+    fn parts(&self) -> (&'static str, &str, &[String]) {
+        match self {
+            ApiError::NotFound {
+                operation,
+                messages,
+            } => ("Not found", operation, messages),
+            ApiError::PermissionDenied {
+                operation,
+                messages,
+            } => ("Permission denied", operation, messages),
+            ApiError::RateLimited {
+                operation,
+                messages,
+            } => ("Rate limited", operation, messages),
+            ApiError::Validation {
+                operation,
+                messages,
+            } => ("Invalid request", operation, messages),
+            ApiError::Transport {
+                operation,
+                messages,
+            } => ("Request failed", operation, messages),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, operation, messages) = self.parts();
+        if operation.is_empty() {
+            write!(f, "{kind}: {}", messages.join("; "))
+        } else {
+            write!(f, "{kind} in {operation}: {}", messages.join("; "))
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
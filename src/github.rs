@@ -1,11 +1,59 @@
-use anyhow::{anyhow, Error};
-use graphql_client::{reqwest::post_graphql_blocking as post_graphql, GraphQLQuery};
+use graphql_client::GraphQLQuery;
 use reqwest::blocking::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::OnceLock;
 
-pub const URL: &str = "https://api.github.com/graphql";
+use crate::{
+    app_auth::AppAuth,
+    config::{OwnerKind, ProjectRef},
+    error::ApiError,
+    request::post_graphql_with_token,
+};
 
+const DEFAULT_URL: &str = "https://api.github.com/graphql";
+static URL: OnceLock<String> = OnceLock::new();
+static APP_AUTH: OnceLock<AppAuth> = OnceLock::new();
+
+/// Point every GH call at a different GraphQL endpoint, i.e. a GHES install's. Only the first call
+/// has any effect, so set it before making any requests.
+pub fn set_url(url: String) {
+    let _ = URL.set(url);
+}
+
+fn url() -> &'static str {
+    URL.get().map(String::as_str).unwrap_or(DEFAULT_URL)
+}
+
+/// The REST API root that goes with the GraphQL endpoint, `https://api.github.com` for github.com
+/// and `https://<host>/api/v3` for GHES.
+pub fn rest_url() -> String {
+    let base = url().trim_end_matches('/').trim_end_matches("/graphql");
+    if base.ends_with("/api") {
+        format!("{base}/v3")
+    } else {
+        base.to_string()
+    }
+}
+
+/// Authenticate every GH call as a GitHub App installation, for clients built without a token.
+pub fn set_app_auth(auth: AppAuth) {
+    let _ = APP_AUTH.set(auth);
+}
+
+// every GH call goes through here so the app's installation token can be added when there is one
+fn post<Q: GraphQLQuery>(
+    client: &Client,
+    variables: Q::Variables,
+) -> Result<Q::ResponseData, ApiError> {
+    let token = APP_AUTH.get().map(|auth| move || auth.token());
+    post_graphql_with_token::<Q, _>(client, url(), variables, token.as_ref().map(|t| t as _))
+}
+
+// scalar names have to match the schema
+#[allow(clippy::upper_case_acronyms)]
 type URI = String;
 type DateTime = String;
+type Date = String;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -20,7 +68,7 @@ pub fn get_issue_or_pr(
     organization: &str,
     repo_name: &str,
     issue_number: i64,
-) -> Result<get_issue_or_pr::GetIssueOrPrRepositoryIssueOrPullRequest, Error> {
+) -> Result<get_issue_or_pr::GetIssueOrPrRepositoryIssueOrPullRequest, ApiError> {
     use get_issue_or_pr::*;
 
     let variables = Variables {
@@ -28,16 +76,17 @@ pub fn get_issue_or_pr(
         owner: organization.to_string(),
         number: issue_number,
     };
-    let response_body = post_graphql::<GetIssueOrPr, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body
-        .data
-        .expect("Expected data in the get issue/pr response from GH.");
-    let response_repo = response_data
-        .repository
-        .ok_or_else(|| anyhow!("missing repository"))?;
-    response_repo
-        .issue_or_pull_request
-        .ok_or_else(|| anyhow!("missing any node"))
+    let response_data: ResponseData = post::<GetIssueOrPr>(&client, variables)?;
+    let response_repo = response_data.repository.ok_or_else(|| {
+        ApiError::not_found(&format!(
+            "GH repository {organization}/{repo_name} doesn't exist."
+        ))
+    })?;
+    response_repo.issue_or_pull_request.ok_or_else(|| {
+        ApiError::not_found(&format!(
+            "GH issue or PR {organization}/{repo_name}#{issue_number} doesn't exist."
+        ))
+    })
 }
 
 #[derive(GraphQLQuery)]
@@ -48,61 +97,94 @@ pub fn get_issue_or_pr(
 )]
 pub struct GetProject;
 
-pub fn get_project_id(
-    client: Client,
-    organization: &str,
-    project_number: i64,
-) -> Result<String, Error> {
-    use get_project::*;
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/get_user_project.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetUserProject;
 
-    let variables = Variables {
-        project_number,
-        organization: organization.to_string(),
+// organization and user projects are looked up the same way, just under a different owner
+pub fn get_project_id(client: Client, project: &ProjectRef) -> Result<String, ApiError> {
+    let project_v2 = match project.owner_kind {
+        OwnerKind::Organization => {
+            use get_project::*;
+
+            let variables = Variables {
+                project_number: project.number,
+                organization: project.owner.clone(),
+            };
+            let response_data: ResponseData = post::<GetProject>(&client, variables)?;
+            response_data
+                .organization
+                .ok_or_else(|| ApiError::not_found("The organization does not exist."))?
+                .project_v2
+                .map(|p| p.id)
+        }
+        OwnerKind::User => {
+            use get_user_project::*;
+
+            let variables = Variables {
+                project_number: project.number,
+                user: project.owner.clone(),
+            };
+            let response_data: ResponseData = post::<GetUserProject>(&client, variables)?;
+            response_data
+                .user
+                .ok_or_else(|| ApiError::not_found("The user does not exist."))?
+                .project_v2
+                .map(|p| p.id)
+        }
     };
-    let response_body = post_graphql::<GetProject, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body.data.expect("Expected GH field data.");
-    Ok(response_data
-        .organization
-        .expect("The organization does not exist.")
-        .project_v2
-        .expect("The project does not exist.")
-        .id)
+    project_v2.ok_or_else(|| ApiError::not_found("The project does not exist."))
 }
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "schemas/github.graphql",
     query_path = "queries/github/get_fields.graphql",
-    response_derives = "Debug, Clone"
+    response_derives = "Debug, Clone, Serialize"
 )]
 pub struct GetFields;
 
 pub fn get_fields(
     client: Client,
     project_id: &str,
-) -> Result<Vec<get_fields::GetFieldsNodeOnProjectV2FieldsNodes>, Error> {
+) -> Result<Vec<get_fields::GetFieldsNodeOnProjectV2FieldsNodes>, ApiError> {
     use get_fields::*;
 
-    let variables = Variables {
-        project_id: project_id.to_string(),
-    };
-    let response_body = post_graphql::<GetFields, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body.data.expect("Expected GH field data.");
-    match response_data
-        .node
-        .expect("Expected GH field nodes back in response.")
-    {
-        GetFieldsNode::ProjectV2(fields) => Ok(fields
-            .fields
-            .nodes
-            .expect("Found no fields for the given GH project.")
-            .into_iter()
-            .flatten()
-            .collect()),
-        _ => Err(anyhow::anyhow!(
-            "Recieved non-ProjectV2 fields back from get fields request to GH."
-        )),
+    let mut fields = vec![];
+    let mut has_next_page = true;
+    let mut end_cursor = None;
+    while has_next_page {
+        let variables = Variables {
+            project_id: project_id.to_string(),
+            end_cursor: end_cursor.clone(),
+        };
+        let response_data: ResponseData = post::<GetFields>(&client, variables)?;
+        let page = match response_data
+            .node
+            .ok_or_else(|| ApiError::not_found("The GH project doesn't exist."))?
+        {
+            GetFieldsNode::ProjectV2(project) => project.fields,
+            _ => {
+                return Err(ApiError::missing(
+                    "Recieved non-ProjectV2 fields back from get fields request to GH.",
+                ))
+            }
+        };
+        has_next_page = page.page_info.has_next_page;
+        end_cursor = page.page_info.end_cursor;
+        fields.extend(
+            page.nodes
+                .ok_or_else(|| ApiError::missing("Found no fields for the given GH project."))?
+                .into_iter()
+                .flatten(),
+        );
     }
+
+    Ok(fields)
 }
 
 #[derive(GraphQLQuery)]
@@ -113,20 +195,19 @@ pub fn get_fields(
 )]
 pub struct AddItem;
 
-pub fn add_item(client: Client, project_id: &str, issue_id: &str) -> Result<String, Error> {
+pub fn add_item(client: Client, project_id: &str, issue_id: &str) -> Result<String, ApiError> {
     use add_item::*;
 
     let variables = Variables {
         project_id: project_id.to_string(),
         issue_id: issue_id.to_string(),
     };
-    let response_body = post_graphql::<AddItem, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body.data.expect("Expected ID for added GH item.");
+    let response_data: ResponseData = post::<AddItem>(&client, variables)?;
     Ok(response_data
         .add_project_v2_item_by_id
-        .expect("GH add item response is missing.")
+        .ok_or_else(|| ApiError::missing("GH add item response is missing."))?
         .item
-        .expect("GH item add response is missing the item.")
+        .ok_or_else(|| ApiError::missing("GH item add response is missing the item."))?
         .id)
 }
 
@@ -145,7 +226,7 @@ pub fn set_field_value(
     item_id: &str,
     field_id: &str,
     value: Option<String>,
-) -> Result<String, Error> {
+) -> Result<String, ApiError> {
     use set_field_value::*;
 
     let variables = Variables {
@@ -154,15 +235,12 @@ pub fn set_field_value(
         value,
         project_id: project_id.to_string(),
     };
-    let response_body = post_graphql::<SetFieldValue, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body
-        .data
-        .expect("Expected ID for set GH field value.");
+    let response_data: ResponseData = post::<SetFieldValue>(&client, variables)?;
     Ok(response_data
         .update_project_v2_item_field_value
-        .expect("GH set field value response is missing.")
+        .ok_or_else(|| ApiError::missing("GH set field value response is missing."))?
         .project_v2_item
-        .expect("GH set field value missing ID.")
+        .ok_or_else(|| ApiError::missing("GH set field value missing ID."))?
         .id)
 }
 
@@ -181,7 +259,7 @@ pub fn set_field_option(
     item_id: &str,
     field_id: &str,
     option_id: Option<String>,
-) -> Result<String, Error> {
+) -> Result<String, ApiError> {
     use set_field_option::*;
 
     let variables = Variables {
@@ -190,15 +268,12 @@ pub fn set_field_option(
         value: option_id,
         project_id: project_id.to_string(),
     };
-    let response_body = post_graphql::<SetFieldOption, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body
-        .data
-        .expect("Expected ID for set GH field option.");
+    let response_data: ResponseData = post::<SetFieldOption>(&client, variables)?;
     Ok(response_data
         .update_project_v2_item_field_value
-        .expect("GH set field option response is missing.")
+        .ok_or_else(|| ApiError::missing("GH set field option response is missing."))?
         .project_v2_item
-        .expect("GH set field option missing ID.")
+        .ok_or_else(|| ApiError::missing("GH set field option missing ID."))?
         .id)
 }
 
@@ -217,7 +292,7 @@ pub fn set_field_number(
     item_id: &str,
     field_id: &str,
     value: Option<f64>,
-) -> Result<String, Error> {
+) -> Result<String, ApiError> {
     use set_field_number::*;
 
     let variables = Variables {
@@ -226,14 +301,524 @@ pub fn set_field_number(
         value,
         project_id: project_id.to_string(),
     };
-    let response_body = post_graphql::<SetFieldNumber, _>(&client, URL, variables)?;
-    let response_data: ResponseData = response_body
-        .data
-        .expect("Expected ID for set GH field number.");
+    let response_data: ResponseData = post::<SetFieldNumber>(&client, variables)?;
+    Ok(response_data
+        .update_project_v2_item_field_value
+        .ok_or_else(|| ApiError::missing("GH set field number response is missing."))?
+        .project_v2_item
+        .ok_or_else(|| ApiError::missing("GH set field number missing ID."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/get_project_items.graphql",
+    response_derives = "Debug, Clone"
+)]
+pub struct GetProjectItems;
+
+pub fn get_project_items(
+    client: Client,
+    project_id: &str,
+) -> Result<Vec<get_project_items::GetProjectItemsNodeOnProjectV2ItemsNodes>, ApiError> {
+    use get_project_items::*;
+
+    let mut project_items = vec![];
+    let mut has_next_page = true;
+    let mut end_cursor = None;
+    while has_next_page {
+        let variables = Variables {
+            project_id: project_id.to_string(),
+            end_cursor: end_cursor.clone(),
+        };
+        let response_data: ResponseData = post::<GetProjectItems>(&client, variables)?;
+        let items = match response_data
+            .node
+            .ok_or_else(|| ApiError::not_found("The GH project doesn't exist."))?
+        {
+            GetProjectItemsNode::ProjectV2(project) => project.items,
+            _ => {
+                return Err(ApiError::missing(
+                    "Recieved non-ProjectV2 node back from get project items request to GH.",
+                ))
+            }
+        };
+        has_next_page = items.page_info.has_next_page;
+        end_cursor = items.page_info.end_cursor;
+        project_items.extend(items.nodes.unwrap_or_default().into_iter().flatten());
+    }
+
+    Ok(project_items)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/set_field_iteration.graphql",
+    response_derives = "Debug"
+)]
+pub struct SetFieldIteration;
+
+pub fn set_field_iteration(
+    client: Client,
+    project_id: &str,
+    // must be the ProjectV2Item ID, not the Issue/PR item ID
+    item_id: &str,
+    field_id: &str,
+    iteration_id: Option<String>,
+) -> Result<String, ApiError> {
+    use set_field_iteration::*;
+
+    let variables = Variables {
+        item_id: item_id.to_string(),
+        field_id: field_id.to_string(),
+        value: iteration_id,
+        project_id: project_id.to_string(),
+    };
+    let response_data: ResponseData = post::<SetFieldIteration>(&client, variables)?;
     Ok(response_data
         .update_project_v2_item_field_value
-        .expect("GH set field number response is missing.")
+        .ok_or_else(|| ApiError::missing("GH set field iteration response is missing."))?
         .project_v2_item
-        .expect("GH set field number missing ID.")
+        .ok_or_else(|| ApiError::missing("GH set field iteration missing ID."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/add_sub_issue.graphql",
+    response_derives = "Debug"
+)]
+pub struct AddSubIssue;
+
+pub fn add_sub_issue(
+    client: Client,
+    issue_id: &str,
+    sub_issue_id: &str,
+) -> Result<String, ApiError> {
+    use add_sub_issue::*;
+
+    let variables = Variables {
+        issue_id: issue_id.to_string(),
+        sub_issue_id: sub_issue_id.to_string(),
+    };
+    let response_data: ResponseData = post::<AddSubIssue>(&client, variables)?;
+    Ok(response_data
+        .add_sub_issue
+        .ok_or_else(|| ApiError::missing("GH add sub-issue response is missing."))?
+        .sub_issue
+        .ok_or_else(|| ApiError::missing("GH add sub-issue response is missing the sub-issue."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/add_comment.graphql",
+    response_derives = "Debug"
+)]
+pub struct AddComment;
+
+pub fn add_comment(client: Client, subject_id: &str, body: &str) -> Result<String, ApiError> {
+    use add_comment::*;
+
+    let variables = Variables {
+        subject_id: subject_id.to_string(),
+        body: body.to_string(),
+    };
+    let response_data: ResponseData = post::<AddComment>(&client, variables)?;
+    Ok(response_data
+        .add_comment
+        .ok_or_else(|| ApiError::missing("GH add comment response is missing."))?
+        .comment_edge
+        .ok_or_else(|| ApiError::missing("GH add comment response is missing the comment."))?
+        .node
+        .ok_or_else(|| ApiError::missing("GH add comment response is missing the comment node."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/update_item_position.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateItemPosition;
+
+// `after_id` of `None` moves the item to the top of the project
+pub fn update_item_position(
+    client: Client,
+    project_id: &str,
+    item_id: &str,
+    after_id: Option<&str>,
+) -> Result<(), ApiError> {
+    use update_item_position::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        item_id: item_id.to_string(),
+        after_id: after_id.map(|id| id.to_string()),
+    };
+    post::<UpdateItemPosition>(&client, variables)?
+        .update_project_v2_item_position
+        .ok_or_else(|| ApiError::missing("GH update item position response is missing."))?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/archive_item.graphql",
+    response_derives = "Debug"
+)]
+pub struct ArchiveItem;
+
+pub fn archive_item(client: Client, project_id: &str, item_id: &str) -> Result<String, ApiError> {
+    use archive_item::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        item_id: item_id.to_string(),
+    };
+    let response_data: ResponseData = post::<ArchiveItem>(&client, variables)?;
+    Ok(response_data
+        .archive_project_v2_item
+        .ok_or_else(|| ApiError::missing("GH archive item response is missing."))?
+        .item
+        .ok_or_else(|| ApiError::missing("GH archive item response is missing the item."))?
         .id)
 }
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/clear_field.graphql",
+    response_derives = "Debug"
+)]
+pub struct ClearField;
+
+pub fn clear_field(
+    client: Client,
+    project_id: &str,
+    // must be the ProjectV2Item ID, not the Issue/PR item ID
+    item_id: &str,
+    field_id: &str,
+) -> Result<String, ApiError> {
+    use clear_field::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        item_id: item_id.to_string(),
+        field_id: field_id.to_string(),
+    };
+    let response_data: ResponseData = post::<ClearField>(&client, variables)?;
+    Ok(response_data
+        .clear_project_v2_item_field_value
+        .ok_or_else(|| ApiError::missing("GH clear field response is missing."))?
+        .project_v2_item
+        .ok_or_else(|| ApiError::missing("GH clear field missing ID."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/delete_item.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteItem;
+
+pub fn delete_item(client: Client, project_id: &str, item_id: &str) -> Result<(), ApiError> {
+    use delete_item::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        item_id: item_id.to_string(),
+    };
+    post::<DeleteItem>(&client, variables)?
+        .delete_project_v2_item
+        .ok_or_else(|| ApiError::missing("GH delete item response is missing."))?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/unarchive_item.graphql",
+    response_derives = "Debug"
+)]
+pub struct UnarchiveItem;
+
+pub fn unarchive_item(client: Client, project_id: &str, item_id: &str) -> Result<String, ApiError> {
+    use unarchive_item::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        item_id: item_id.to_string(),
+    };
+    let response_data: ResponseData = post::<UnarchiveItem>(&client, variables)?;
+    Ok(response_data
+        .unarchive_project_v2_item
+        .ok_or_else(|| ApiError::missing("GH unarchive item response is missing."))?
+        .item
+        .ok_or_else(|| ApiError::missing("GH unarchive item response is missing the item."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/remove_sub_issue.graphql",
+    response_derives = "Debug"
+)]
+pub struct RemoveSubIssue;
+
+pub fn remove_sub_issue(
+    client: Client,
+    issue_id: &str,
+    sub_issue_id: &str,
+) -> Result<String, ApiError> {
+    use remove_sub_issue::*;
+
+    let variables = Variables {
+        issue_id: issue_id.to_string(),
+        sub_issue_id: sub_issue_id.to_string(),
+    };
+    let response_data: ResponseData = post::<RemoveSubIssue>(&client, variables)?;
+    Ok(response_data
+        .remove_sub_issue
+        .ok_or_else(|| ApiError::missing("GH remove sub-issue response is missing."))?
+        .sub_issue
+        .ok_or_else(|| ApiError::missing("GH remove sub-issue response is missing the sub-issue."))?
+        .id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/delete_comment.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteComment;
+
+pub fn delete_comment(client: Client, comment_id: &str) -> Result<(), ApiError> {
+    use delete_comment::*;
+
+    let variables = Variables {
+        id: comment_id.to_string(),
+    };
+    post::<DeleteComment>(&client, variables)?
+        .delete_issue_comment
+        .ok_or_else(|| ApiError::missing("GH delete comment response is missing."))?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/get_repository_access.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetRepositoryAccess;
+
+/// How much the token can do in a repository.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepositoryAccess {
+    /// It can't see the repository at all.
+    None,
+    /// It can read issues, but not manage them.
+    Read,
+    /// It can manage issues, i.e. link sub-issues.
+    ManageIssues,
+    /// GH didn't say what the token's permission is.
+    Unknown,
+}
+
+// repositories the token can't see come back as not found rather than forbidden
+pub fn repository_access(
+    client: Client,
+    owner: &str,
+    name: &str,
+) -> Result<RepositoryAccess, ApiError> {
+    use get_repository_access::*;
+
+    let variables = Variables {
+        owner: owner.to_string(),
+        name: name.to_string(),
+    };
+    let repository = match post::<GetRepositoryAccess>(&client, variables) {
+        Ok(response_data) => response_data.repository,
+        Err(e) if e.is_not_found() => None,
+        Err(e) => return Err(e),
+    };
+    Ok(match repository.map(|r| r.viewer_permission) {
+        None => RepositoryAccess::None,
+        Some(Some(RepositoryPermission::READ)) => RepositoryAccess::Read,
+        Some(Some(
+            RepositoryPermission::TRIAGE
+            | RepositoryPermission::WRITE
+            | RepositoryPermission::MAINTAIN
+            | RepositoryPermission::ADMIN,
+        )) => RepositoryAccess::ManageIssues,
+        Some(_) => RepositoryAccess::Unknown,
+    })
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/get_project_access.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetProjectAccess;
+
+pub fn can_update_project(client: Client, project_id: &str) -> Result<bool, ApiError> {
+    use get_project_access::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+    };
+    match post::<GetProjectAccess>(&client, variables)?
+        .node
+        .ok_or_else(|| ApiError::not_found("The project does not exist."))?
+    {
+        GetProjectAccessNode::ProjectV2(project) => Ok(project.viewer_can_update),
+        _ => Err(ApiError::missing(
+            "Recieved a non-ProjectV2 node back from the project access request to GH.",
+        )),
+    }
+}
+
+/// The colours GH has for single select options.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OptionColor {
+    Blue,
+    Gray,
+    Green,
+    Orange,
+    Pink,
+    Purple,
+    Red,
+    Yellow,
+}
+
+/// A single select option to create, or to keep when a field's options are replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectOption {
+    pub name: String,
+    pub color: OptionColor,
+    pub description: String,
+}
+
+/// The field types that can be created, iteration fields can only be made in the GH UI.
+#[derive(Debug, Clone, Copy)]
+pub enum NewFieldType {
+    Number,
+    Text,
+    SingleSelect,
+}
+
+// every mutation generates its own option input type, they all have the same shape as `SelectOption`
+fn option_inputs<T: DeserializeOwned>(
+    operation: &str,
+    options: &[SelectOption],
+) -> Result<Vec<T>, ApiError> {
+    serde_json::to_value(options)
+        .and_then(serde_json::from_value)
+        .map_err(|e| ApiError::transport(operation, e))
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/create_field.graphql",
+    response_derives = "Debug",
+    variables_derives = "Deserialize"
+)]
+pub struct CreateField;
+
+pub fn create_field(
+    client: Client,
+    project_id: &str,
+    name: &str,
+    data_type: NewFieldType,
+    options: &[SelectOption],
+) -> Result<String, ApiError> {
+    use create_field::*;
+
+    let variables = Variables {
+        project_id: project_id.to_string(),
+        name: name.to_string(),
+        data_type: match data_type {
+            NewFieldType::Number => ProjectV2CustomFieldType::NUMBER,
+            NewFieldType::Text => ProjectV2CustomFieldType::TEXT,
+            NewFieldType::SingleSelect => ProjectV2CustomFieldType::SINGLE_SELECT,
+        },
+        options: match data_type {
+            NewFieldType::SingleSelect => Some(option_inputs("CreateField", options)?),
+            _ => None,
+        },
+    };
+    let response_data: ResponseData = post::<CreateField>(&client, variables)?;
+    match response_data
+        .create_project_v2_field
+        .ok_or_else(|| ApiError::missing("GH create field response is missing."))?
+        .project_v2_field
+        .ok_or_else(|| ApiError::missing("GH create field response is missing the field."))?
+    {
+        CreateFieldCreateProjectV2FieldProjectV2Field::ProjectV2Field(f) => Ok(f.id),
+        CreateFieldCreateProjectV2FieldProjectV2Field::ProjectV2SingleSelectField(f) => Ok(f.id),
+        CreateFieldCreateProjectV2FieldProjectV2Field::ProjectV2IterationField(f) => Ok(f.id),
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/delete_field.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteField;
+
+pub fn delete_field(client: Client, field_id: &str) -> Result<(), ApiError> {
+    use delete_field::*;
+
+    let variables = Variables {
+        field_id: field_id.to_string(),
+    };
+    post::<DeleteField>(&client, variables)?
+        .delete_project_v2_field
+        .ok_or_else(|| ApiError::missing("GH delete field response is missing."))?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/github.graphql",
+    query_path = "queries/github/update_field_options.graphql",
+    response_derives = "Debug",
+    variables_derives = "Deserialize"
+)]
+pub struct UpdateFieldOptions;
+
+// `options` replaces every option the field has, which gives them new IDs and clears them from
+// every item
+pub fn update_field_options(
+    client: Client,
+    field_id: &str,
+    options: &[SelectOption],
+) -> Result<(), ApiError> {
+    use update_field_options::*;
+
+    let variables = Variables {
+        field_id: field_id.to_string(),
+        options: Some(option_inputs("UpdateFieldOptions", options)?),
+    };
+    post::<UpdateFieldOptions>(&client, variables)?
+        .update_project_v2_field
+        .ok_or_else(|| ApiError::missing("GH update field response is missing."))?;
+    Ok(())
+}
@@ -0,0 +1,540 @@
+use anyhow::{Context, Error};
+use chrono::Utc;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use crate::{
+    create_fields,
+    error::ApiError,
+    github::{self, SelectOption},
+    sync::{set_field, FieldValue, IssueKey},
+};
+
+/// Append-only record of every mutation a sync makes, one JSON object per line. Each line is flushed
+/// as soon as its mutation succeeds, so the journal is complete up to the point a run died.
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    project_id: String,
+    api_url: String,
+}
+
+/// A line in the journal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalLine {
+    pub at: String,
+    pub project_id: String,
+    // GH GraphQL endpoint the mutation was made against, missing from older journals
+    #[serde(default)]
+    pub api_url: Option<String>,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+/// A mutation that was made, with what's needed to undo it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "mutation", rename_all = "snake_case")]
+pub enum Entry {
+    AddItem {
+        issue: IssueKey,
+        item_id: String,
+        content_id: String,
+    },
+    SetField {
+        issue: IssueKey,
+        item_id: String,
+        field_id: String,
+        field_name: String,
+        previous: FieldValue,
+        value: FieldValue,
+    },
+    AddSubIssue {
+        parent: IssueKey,
+        child: IssueKey,
+        parent_id: String,
+        child_id: String,
+    },
+    AddComment {
+        issue: IssueKey,
+        comment_id: String,
+    },
+    MoveItem {
+        issue: IssueKey,
+        item_id: String,
+        after_item_id: Option<String>,
+    },
+    ArchiveItem {
+        issue: IssueKey,
+        item_id: String,
+    },
+    CreateField {
+        field_id: String,
+        field_name: String,
+    },
+    // replacing a field's options gives them new IDs and clears them from every item, so the items'
+    // options are kept by name to be put back
+    ReplaceOptions {
+        field_id: String,
+        field_name: String,
+        previous: Vec<SelectOption>,
+        values: Vec<OptionValue>,
+    },
+    // an item's option put back after its field's options were replaced, undone along with them
+    RestoreOption {
+        item_id: String,
+        field_id: String,
+        field_name: String,
+        option: String,
+    },
+}
+
+/// The option an item had on a single select field, by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionValue {
+    pub item_id: String,
+    pub option: String,
+}
+
+impl Journal {
+    pub fn open(path: &Path, project_id: &str, api_url: &str) -> Result<Journal, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Couldn't open journal file {}.", path.display()))?;
+        Ok(Journal {
+            file,
+            path: path.to_path_buf(),
+            project_id: project_id.to_string(),
+            api_url: api_url.to_string(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, entry: Entry) -> Result<(), Error> {
+        let line = JournalLine {
+            at: Utc::now().to_rfc3339(),
+            project_id: self.project_id.clone(),
+            api_url: Some(self.api_url.clone()),
+            entry,
+        };
+        let mut json = serde_json::to_vec(&line)?;
+        json.push(b'\n');
+        self.file
+            .write_all(&json)
+            .and_then(|_| self.file.sync_data())
+            .with_context(|| format!("Couldn't write to journal file {}.", self.path.display()))
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<JournalLine>, Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read journal file {}.", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "Couldn't parse line {} of journal file {}.",
+                    n + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Undo everything in a journal, newest first. Items and fields the journal added are deleted outright,
+/// so the field changes on them aren't undone one by one. Replaced options are replaced again with the
+/// old ones, and every item gets back the option it had by name. Moves can't be undone since the journal doesn't
+/// know where items were before, they're only counted. Anything that's already gone counts as undone,
+/// so a rollback that was cut short can be run again. Other failures are collected and the rest of
+/// the journal is still undone, unless `fail_fast` is set.
+pub fn rollback(
+    client: Client,
+    lines: Vec<JournalLine>,
+    fail_fast: bool,
+) -> Result<Rollback, Error> {
+    let (lines, skipped) = to_undo(lines);
+    let mut rollback = Rollback {
+        skipped,
+        ..Rollback::default()
+    };
+    for line in lines {
+        let project_id = &line.project_id;
+        let (mutation, result) = match line.entry {
+            Entry::AddItem { issue, item_id, .. } => {
+                info!(%issue, %item_id, "Removing the item from the project");
+                (
+                    format!("remove {issue} from the project"),
+                    github::delete_item(client.clone(), project_id, &item_id),
+                )
+            }
+            Entry::SetField {
+                issue,
+                item_id,
+                field_id,
+                field_name,
+                previous,
+                ..
+            } => {
+                info!(%issue, %item_id, field = %field_name, "Restoring the field");
+                (
+                    format!("restore {issue} {field_name}"),
+                    set_field(client.clone(), project_id, &item_id, &field_id, previous).map(drop),
+                )
+            }
+            Entry::AddSubIssue {
+                parent,
+                child,
+                parent_id,
+                child_id,
+            } => {
+                info!(%parent, %child, "Removing the sub-issue");
+                (
+                    format!("remove {child} as a sub-issue of {parent}"),
+                    github::remove_sub_issue(client.clone(), &parent_id, &child_id).map(drop),
+                )
+            }
+            Entry::AddComment { issue, comment_id } => {
+                info!(%issue, %comment_id, "Deleting the comment");
+                (
+                    format!("delete the comment on {issue}"),
+                    github::delete_comment(client.clone(), &comment_id),
+                )
+            }
+            Entry::ArchiveItem { issue, item_id } => {
+                info!(%issue, %item_id, "Unarchiving the item");
+                (
+                    format!("unarchive {issue}"),
+                    github::unarchive_item(client.clone(), project_id, &item_id).map(drop),
+                )
+            }
+            Entry::CreateField {
+                field_id,
+                field_name,
+            } => {
+                info!(%field_id, field = %field_name, "Deleting the field");
+                (
+                    format!("delete the {field_name} field"),
+                    github::delete_field(client.clone(), &field_id),
+                )
+            }
+            Entry::ReplaceOptions {
+                field_id,
+                field_name,
+                previous,
+                values,
+            } => {
+                info!(field = %field_name, items = values.len(), "Restoring the field's options");
+                (
+                    format!("restore the {field_name} options"),
+                    restore_options(client.clone(), project_id, &field_id, &previous, &values),
+                )
+            }
+            Entry::MoveItem { .. } | Entry::RestoreOption { .. } => {
+                unreachable!("moves and restored options are never undone")
+            }
+        };
+        match result {
+            Ok(()) => rollback.undone += 1,
+            // i.e. undone by an earlier rollback that didn't finish
+            Err(e) if e.is_not_found() => {
+                info!(%mutation, error = %e, "Already undone");
+                rollback.undone += 1;
+            }
+            Err(e) if fail_fast => {
+                return Err(Error::from(e).context(format!("Couldn't {mutation}.")));
+            }
+            Err(e) => {
+                warn!(%mutation, error = %e, "Failed, carrying on");
+                rollback.failures.push(RollbackFailure {
+                    mutation,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok(rollback)
+}
+
+// Put a field's old options back, then each item's option by name since replacing the options clears
+// them. Items that have been deleted since are left out.
+fn restore_options(
+    client: Client,
+    project_id: &str,
+    field_id: &str,
+    options: &[SelectOption],
+    values: &[OptionValue],
+) -> Result<(), ApiError> {
+    github::update_field_options(client.clone(), field_id, options)?;
+    let option_ids = create_fields::option_ids(client.clone(), project_id, field_id)?;
+    for value in values {
+        let option_id = option_ids.get(&value.option).cloned();
+        match set_field(
+            client.clone(),
+            project_id,
+            &value.item_id,
+            field_id,
+            FieldValue::SingleSelect(option_id),
+        ) {
+            Err(e) if !e.is_not_found() => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// The lines that need undoing, newest first, and how many moves were left out. Changes to items and
+// fields the journal added go away with them, so they're left out too.
+fn to_undo(lines: Vec<JournalLine>) -> (Vec<JournalLine>, usize) {
+    let added_items: HashSet<String> = lines
+        .iter()
+        .filter_map(|line| match &line.entry {
+            Entry::AddItem { item_id, .. } => Some(item_id.clone()),
+            _ => None,
+        })
+        .collect();
+    let created_fields: HashSet<String> = lines
+        .iter()
+        .filter_map(|line| match &line.entry {
+            Entry::CreateField { field_id, .. } => Some(field_id.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut moves = 0;
+    let lines = lines
+        .into_iter()
+        .rev()
+        .filter(|line| match &line.entry {
+            Entry::SetField { item_id, .. } | Entry::ArchiveItem { item_id, .. }
+                if added_items.contains(item_id) =>
+            {
+                false
+            }
+            Entry::SetField { field_id, .. } => !created_fields.contains(field_id),
+            Entry::MoveItem { .. } => {
+                moves += 1;
+                false
+            }
+            // undoing the replaced options puts every item's option back
+            Entry::RestoreOption { .. } => false,
+            _ => true,
+        })
+        .collect();
+    (lines, moves)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Rollback {
+    pub undone: usize,
+    // moves, which can't be undone
+    pub skipped: usize,
+    pub failures: Vec<RollbackFailure>,
+}
+
+/// A mutation that couldn't be undone, the rest of the rollback carries on without it.
+#[derive(Debug, Serialize)]
+pub struct RollbackFailure {
+    // what was being undone, i.e. "unarchive owner/repo#1"
+    pub mutation: String,
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(number: i64) -> IssueKey {
+        IssueKey {
+            owner: "acme".to_string(),
+            repo: "app".to_string(),
+            number,
+        }
+    }
+
+    fn line(entry: Entry) -> JournalLine {
+        JournalLine {
+            at: "2024-03-01T00:00:00+00:00".to_string(),
+            project_id: "project".to_string(),
+            api_url: None,
+            entry,
+        }
+    }
+
+    fn set_field(number: i64, field_id: &str) -> Entry {
+        Entry::SetField {
+            issue: issue(number),
+            item_id: format!("item-{number}"),
+            field_id: field_id.to_string(),
+            field_name: field_id.to_string(),
+            previous: FieldValue::Text(None),
+            value: FieldValue::Text(Some("Blocks acme/app#2".to_string())),
+        }
+    }
+
+    // a short description of each line to undo, and the number of moves left out
+    fn undo(entries: Vec<Entry>) -> (Vec<String>, usize) {
+        let (lines, skipped) = to_undo(entries.into_iter().map(line).collect());
+        let lines = lines
+            .into_iter()
+            .map(|line| match line.entry {
+                Entry::AddItem { item_id, .. } => format!("add {item_id}"),
+                Entry::SetField {
+                    item_id, field_id, ..
+                } => format!("set {item_id} {field_id}"),
+                Entry::AddSubIssue { child_id, .. } => format!("sub-issue {child_id}"),
+                Entry::AddComment { comment_id, .. } => format!("comment {comment_id}"),
+                Entry::MoveItem { item_id, .. } => format!("move {item_id}"),
+                Entry::ArchiveItem { item_id, .. } => format!("archive {item_id}"),
+                Entry::CreateField { field_id, .. } => format!("create {field_id}"),
+                Entry::ReplaceOptions { field_id, .. } => format!("replace {field_id}"),
+                Entry::RestoreOption {
+                    item_id, field_id, ..
+                } => format!("restore {item_id} {field_id}"),
+            })
+            .collect();
+        (lines, skipped)
+    }
+
+    #[test]
+    fn to_undo_goes_newest_first() {
+        assert_eq!(
+            undo(vec![
+                set_field(1, "status"),
+                Entry::AddComment {
+                    issue: issue(1),
+                    comment_id: "comment-1".to_string(),
+                },
+                Entry::AddSubIssue {
+                    parent: issue(2),
+                    child: issue(1),
+                    parent_id: "issue-2".to_string(),
+                    child_id: "issue-1".to_string(),
+                },
+            ]),
+            (
+                vec![
+                    "sub-issue issue-1".to_string(),
+                    "comment comment-1".to_string(),
+                    "set item-1 status".to_string(),
+                ],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn to_undo_leaves_out_changes_to_added_items() {
+        assert_eq!(
+            undo(vec![
+                Entry::AddItem {
+                    issue: issue(1),
+                    item_id: "item-1".to_string(),
+                    content_id: "issue-1".to_string(),
+                },
+                set_field(1, "status"),
+                set_field(2, "status"),
+                Entry::ArchiveItem {
+                    issue: issue(1),
+                    item_id: "item-1".to_string(),
+                },
+                Entry::ArchiveItem {
+                    issue: issue(2),
+                    item_id: "item-2".to_string(),
+                },
+            ]),
+            (
+                vec![
+                    "archive item-2".to_string(),
+                    "set item-2 status".to_string(),
+                    "add item-1".to_string(),
+                ],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn to_undo_leaves_out_values_of_created_fields() {
+        assert_eq!(
+            undo(vec![
+                Entry::CreateField {
+                    field_id: "blocking".to_string(),
+                    field_name: "Blocking".to_string(),
+                },
+                set_field(1, "blocking"),
+                set_field(1, "status"),
+            ]),
+            (
+                vec![
+                    "set item-1 status".to_string(),
+                    "create blocking".to_string()
+                ],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn to_undo_puts_options_back_along_with_the_replaced_ones() {
+        assert_eq!(
+            undo(vec![
+                Entry::ReplaceOptions {
+                    field_id: "status".to_string(),
+                    field_name: "Status".to_string(),
+                    previous: vec![],
+                    values: vec![OptionValue {
+                        item_id: "item-1".to_string(),
+                        option: "Todo".to_string(),
+                    }],
+                },
+                Entry::RestoreOption {
+                    item_id: "item-1".to_string(),
+                    field_id: "status".to_string(),
+                    field_name: "Status".to_string(),
+                    option: "Todo".to_string(),
+                },
+                set_field(1, "status"),
+            ]),
+            (
+                vec![
+                    "set item-1 status".to_string(),
+                    "replace status".to_string()
+                ],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn to_undo_counts_moves_without_undoing_them() {
+        assert_eq!(
+            undo(vec![
+                Entry::MoveItem {
+                    issue: issue(1),
+                    item_id: "item-1".to_string(),
+                    after_item_id: None,
+                },
+                Entry::MoveItem {
+                    issue: issue(2),
+                    item_id: "item-2".to_string(),
+                    after_item_id: Some("item-1".to_string()),
+                },
+            ]),
+            (vec![], 2)
+        );
+    }
+}
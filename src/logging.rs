@@ -0,0 +1,30 @@
+use tracing_subscriber::EnvFilter;
+
+use crate::cli::OutputFormat;
+
+/// Send logs to stderr so command output on stdout can still be piped. `-q` only shows warnings and
+/// errors, `-v` adds debug logs and `-vv` traces every API request and response body. `RUST_LOG`
+/// overrides both, i.e. `RUST_LOG=zenhub_to_github_migrator::request=trace`.
+pub fn init(verbosity: i8, format: OutputFormat) {
+    let level = match verbosity {
+        i8::MIN..=-1 => "warn",
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
+    // dependencies stay at warn, their debug logs drown out ours
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}={level}", env!("CARGO_CRATE_NAME"))));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        OutputFormat::Text => builder.with_target(false).init(),
+        // one object per line, with the current issue's span fields for filtering in CI
+        OutputFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
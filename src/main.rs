@@ -1,320 +1,451 @@
-use anyhow::{anyhow, Error};
-use reqwest::blocking::Client;
-use std::{collections::HashMap, env, iter};
+use anyhow::{anyhow, Context, Error};
+use clap::Parser;
+use reqwest::{
+    blocking::{Client, ClientBuilder},
+    Certificate,
+};
+use serde::Serialize;
+use std::{env, fmt, fs, iter, path::PathBuf};
+use tracing::{debug, info, trace};
 
+use app_auth::AppAuth;
+use checkpoint::Checkpoint;
+use cli::{Cli, Command, InspectTarget, OutputFormat};
+use config::{Connection, HttpConfig};
+use journal::Journal;
+
+mod app_auth;
+mod checkpoint;
+mod cli;
+mod config;
+mod create_fields;
+mod error;
 mod github;
+mod journal;
+mod logging;
+mod mapping;
+mod preflight;
+mod report;
+mod request;
+mod sync;
+mod validate;
+mod verify;
 mod zenhub;
 
-fn parse_repo_name(repo_name: &str) -> Result<(&str, &str), Error> {
-    let mut parts = repo_name.split('/');
-    match (parts.next(), parts.next()) {
-        (Some(owner), Some(name)) => Ok((owner, name)),
-        _ => Err(anyhow!("wrong format for the repository name param (we expect something like facebook/graphql)"))
-    }
-}
-
-fn parse_project_id(project_url: &str) -> Result<i64, Error> {
-    project_url
-        .split("projects/")
-        .last()
-        .and_then(|v| v.parse::<i64>().ok())
-        .ok_or_else(|| anyhow!("Provided project URL didn't end with an ID."))
-}
-
-fn main() -> Result<(), Error> {
-    // currently think it needs Issues:RW, Pull Requests:RW, Issue Types:RW, and Projects:RW
-    let github_api_token = env::var("GITHUB_TOKEN").expect("Missing GITHUB_TOKEN.");
-    let zenhub_api_token = env::var("ZENHUB_TOKEN").expect("Missing ZENHUB_TOKEN.");
-
-    // TODO(murph): inputs. Since mappings are required, may want to require passing a config file
-    let organization = "IronCoreLabs";
-    // ZH -> GH
-    let field_mapping = HashMap::from([
-        // Currently supported fields
-        ("Estimate", "Estimate"),
-        ("Priority", "Priority"),
-        ("Pipeline", "Status"),
-        // Not yet supported fields
-        // ("Linked Issues", "Text"),
-        // ("Blocking", "Text"),
-        // ("Sprint", "Iteration"), don't pull this over
-    ]);
-    // ZH -> GH
-    let lane_mapping = HashMap::from([
-        ("Ungroomed", "Ungroomed"),
-        ("Tech Debt", "Tech Debt"),
-        ("Backlog", "Backlog"),
-        ("Next Sprint", "Backlog"),
-        ("This Sprint", "Backlog"),
-        ("In Progress", "In Progress"),
-        ("Review", "Review"),
-    ]);
-    // TODO(murph): priority is hardcoded from "High priority" to P0 because that's all we have (if not null)
-
-    let project_number = parse_project_id("https://github.com/orgs/IronCoreLabs/projects/8")?;
-    let workspace_name = "🍻 The Big Board 🌯";
-
-    let github_client = Client::builder()
-        .user_agent("zenhub-to-github-migrator/0.1.0")
+fn build_client(token_var: &str, http: &HttpConfig) -> Result<Client, Error> {
+    let token = env::var(token_var).map_err(|_| anyhow!("Missing {token_var}."))?;
+    Ok(client_builder(http)?
         .default_headers(
             iter::once((
                 reqwest::header::AUTHORIZATION,
-                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", github_api_token))
-                    .unwrap(),
+                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))?,
             ))
             .collect(),
         )
-        .build()?;
+        .build()?)
+}
 
-    let zenhub_client = Client::builder()
-        .user_agent("zenhub-to-github-migrator/0.1.0")
-        .default_headers(
-            iter::once((
-                reqwest::header::AUTHORIZATION,
-                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", zenhub_api_token))
-                    .unwrap(),
-            ))
-            .collect(),
-        )
-        .build()?;
+// GitHub App installations get a client without a token, the installation token is added to each
+// request instead since it expires during long runs
+fn build_github_client(connection: &Connection) -> Result<Client, Error> {
+    let Some(app) = &connection.github.app else {
+        return build_client("GITHUB_TOKEN", &connection.http);
+    };
+    let client = client_builder(&connection.http)?.build()?;
+    github::set_app_auth(AppAuth::new(app, &github::rest_url(), client.clone())?);
+    Ok(client)
+}
+
+// everything both clients share, apart from auth
+fn client_builder(http: &HttpConfig) -> Result<ClientBuilder, Error> {
+    let mut builder = Client::builder().user_agent("zenhub-to-github-migrator/0.1.0");
+    if let Some(proxy) = &http.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    for path in &http.ca_certificates {
+        let pem = fs::read(path)
+            .with_context(|| format!("Couldn't read CA certificate file {}.", path.display()))?;
+        for certificate in Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Couldn't parse CA certificate file {}.", path.display()))?
+        {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
 
-    let zenhub_workspace = dbg!(zenhub::get_workspace(
-        zenhub_client.clone(),
-        workspace_name
-    )?);
-    let github_project_id = dbg!(github::get_project_id(
-        github_client.clone(),
-        organization,
-        project_number
-    )?);
-    let github_project_fields = dbg!(github::get_fields(
-        github_client.clone(),
-        &github_project_id
-    ))?;
+fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+    logging::init(
+        if cli.global.quiet {
+            -1
+        } else {
+            cli.global.verbose as i8
+        },
+        cli.global.log_format,
+    );
+    let format = cli.global.format;
+    // inspect workspace and rollback don't need a config, but still connect the way it says to when
+    // there is one
+    let connection = config::Connection::load(&cli.global.config)?;
+    if let Some(api_url) = &connection.zenhub.api_url {
+        zenhub::set_url(api_url.clone());
+    }
 
-    for pipeline in zenhub_workspace.pipelines_connection.nodes {
-        println!("Getting issues for Zenhub pipeline {}", pipeline.name);
-        let mut issues = dbg!(zenhub::get_pipeline_issues(
-            zenhub_client.clone(),
-            &pipeline.id,
-            &zenhub_workspace.id
-        ))?;
-        // lazy way to get issues in the same order in GH projects. Position APIs are possible from both if we need something better
-        issues.reverse();
-        println!("Adding issues for Zenhub pipeline {}", pipeline.name);
-        for zh_issue in issues {
-            println!(
-                "Getting GitHub issue {}/{}#{}",
-                &zh_issue.repository.owner.login, &zh_issue.repository.name, zh_issue.number
-            );
-            let maybe_gh_issue = github::get_issue_or_pr(
-                github_client.clone(),
-                &zh_issue.repository.owner.login,
-                &zh_issue.repository.name,
-                zh_issue.number,
-            );
-            match maybe_gh_issue {
-                Err(e) => {
-                    println!("Missing GH issue {}/{}#{}. Usually when this happens the issue or creator of it have been deleted. {e}", zh_issue.repository.owner.login, zh_issue.repository.name, zh_issue.number);
-                    continue;
+    // ZH only, so it doesn't need a config
+    if let Command::Inspect {
+        target: InspectTarget::Workspace,
+    } = cli.command
+    {
+        let zenhub_client = build_client("ZENHUB_TOKEN", &connection.http)?;
+        return inspect_workspace(zenhub_client, format);
+    }
+    // the journal has everything rollback needs, including the project
+    if let Command::Rollback { journal } = &cli.command {
+        let lines = journal::read(journal)?;
+        // undo against the same GH the sync was made against
+        if let Some(api_url) = lines.iter().find_map(|l| l.api_url.clone()) {
+            github::set_url(api_url);
+        }
+        let github_client = build_github_client(&connection)?;
+        let rollback = journal::rollback(github_client, lines, cli.global.fail_fast)?;
+        match format {
+            OutputFormat::Text => {
+                println!(
+                    "Rolled back {} mutations, {} moves can't be undone.",
+                    rollback.undone, rollback.skipped
+                );
+                if !rollback.failures.is_empty() {
+                    println!("Couldn't undo ({}):", rollback.failures.len());
+                    for failure in &rollback.failures {
+                        println!("  {} {}", failure.mutation, failure.error);
+                    }
                 }
-                Ok(gh_issue) => {
-                    let gh_item_id = match gh_issue {
-                        github::get_issue_or_pr::GetIssueOrPrRepositoryIssueOrPullRequest::Issue(issue) => {
-                            issue.id
-                        }
-                        github::get_issue_or_pr::GetIssueOrPrRepositoryIssueOrPullRequest::PullRequest(
-                            pr,
-                        ) => pr.id,
-                    };
-                    println!(
-                        "Adding issue {}/{}#{} to project, GH item ID {}.",
-                        &zh_issue.repository.owner.login,
-                        &zh_issue.repository.name,
-                        zh_issue.number,
-                        gh_item_id
-                    );
-                    let gh_project_item_id =
-                        github::add_item(github_client.clone(), &github_project_id, &gh_item_id)?;
-                    println!(
-                        "Item ID {} added to project, project ID {}.",
-                        gh_item_id, gh_project_item_id
-                    );
-                    let estimate = zh_issue.estimate.map(|e| e.value);
-                    let estimate_field_id =
-                        zh_to_gh_field_id("Estimate", &field_mapping, &github_project_fields)?;
-                    let priority_option_id = zh_to_gh_priority(
-                        zh_issue
-                            .pipeline_issue
-                            .expect("ZH issue missing any priority field.")
-                            .priority
-                            .map(|p| p.name),
-                        &field_mapping,
-                        &github_project_fields,
-                    )?;
-                    // TODO(murph): on this and others can probably do this first or return it from the other method to save time.
-                    let priority_field_id =
-                        zh_to_gh_field_id("Priority", &field_mapping, &github_project_fields)?;
-                    // status field based on current pipeline
-                    let status_option_id = zh_to_gh_status_id(
-                        &pipeline.name,
-                        &lane_mapping,
-                        &field_mapping,
-                        &github_project_fields,
-                    )?;
-                    let status_field_id =
-                        zh_to_gh_field_id("Pipeline", &field_mapping, &github_project_fields)?;
-                    println!(
-                        "Setting item {} estimate to {:?}",
-                        gh_project_item_id, estimate
-                    );
-                    github::set_field_number(
-                        github_client.clone(),
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rollback)?),
+        }
+        if !rollback.failures.is_empty() {
+            return Err(anyhow!(
+                "{} mutation(s) couldn't be undone, run the rollback again once they're fixed.",
+                rollback.failures.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    let config = config::Config::load(&cli.global.config)?;
+    let github_api_url = config.github.api_url()?;
+    github::set_url(github_api_url.clone());
+    // currently think it needs Issues:RW, Pull Requests:RW, Issue Types:RW, and Projects:RW
+    let github_client = build_github_client(&connection)?;
+    let github_project_id =
+        github::get_project_id(github_client.clone(), &config.github.project()?)?;
+    debug!(project_id = %github_project_id, "Found the GitHub project");
+    let mut github_project_fields = github::get_fields(github_client.clone(), &github_project_id)?;
+    debug!(
+        fields = github_project_fields.len(),
+        "Got the GitHub project fields"
+    );
+    trace!(?github_project_fields);
+    if let Command::Inspect {
+        target: InspectTarget::Project,
+    } = cli.command
+    {
+        return inspect_project(&github_project_fields, format);
+    }
+
+    let zenhub_client = build_client("ZENHUB_TOKEN", &connection.http)?;
+    let zenhub_workspace =
+        zenhub::get_workspace(zenhub_client.clone(), &config.zenhub.workspace_ref()?)?;
+    debug!(workspace_id = %zenhub_workspace.id, "Found the Zenhub workspace");
+    trace!(?zenhub_workspace);
+
+    match cli.command {
+        Command::Validate => {
+            let problems = validate::validate(&config, &zenhub_workspace, &github_project_fields);
+            print_problems(&problems, format, "Config is valid.")?;
+            if !problems.is_empty() {
+                return Err(anyhow!(
+                    "Config has {} problem(s) against the live data.",
+                    problems.len()
+                ));
+            }
+        }
+        Command::Verify => {
+            let problems = verify::verify(
+                github_client,
+                zenhub_client,
+                &config,
+                zenhub_workspace,
+                &github_project_id,
+                &github_project_fields,
+            )?;
+            print_problems(&problems, format, "GH project matches the ZH workspace.")?;
+            if !problems.is_empty() {
+                return Err(anyhow!(
+                    "GH project differs from the ZH workspace in {} way(s).",
+                    problems.len()
+                ));
+            }
+        }
+        Command::Plan { .. } | Command::Sync { .. } => {
+            let field_plan = match cli.command {
+                Command::Sync {
+                    create_fields: true,
+                    ..
+                } => create_fields::plan(&config, &zenhub_workspace, &github_project_fields),
+                _ => create_fields::FieldPlan::default(),
+            };
+            // a sync doesn't change anything until it knows it has everything it needs, including
+            // the fields and options it's about to create
+            if let Command::Sync { .. } = cli.command {
+                let problems = preflight::preflight(
+                    github_client.clone(),
+                    zenhub_client.clone(),
+                    &config,
+                    &zenhub_workspace,
+                    &github_project_id,
+                    &create_fields::with_new_fields(&github_project_fields, &field_plan),
+                )?;
+                if !problems.is_empty() {
+                    print_problems(&problems, format, "")?;
+                    return Err(anyhow!(
+                        "Preflight found {} problem(s), nothing was changed.",
+                        problems.len()
+                    ));
+                }
+            }
+            // plans don't write a checkpoint, syncs start a new one unless they're resuming
+            let (checkpoint, resume) = match &cli.command {
+                Command::Sync {
+                    resume: true,
+                    checkpoint,
+                    ..
+                } => (
+                    Some(Checkpoint::load(
+                        checkpoint,
+                        &zenhub_workspace.id,
                         &github_project_id,
-                        &gh_project_item_id,
-                        &estimate_field_id,
-                        estimate,
-                    )?;
-                    println!(
-                        "Setting item {} priority to {:?}",
-                        gh_project_item_id, priority_option_id
-                    );
-                    github::set_field_option(
-                        github_client.clone(),
+                    )?),
+                    true,
+                ),
+                Command::Sync { checkpoint, .. } => (
+                    Some(Checkpoint::new(
+                        checkpoint,
+                        &zenhub_workspace.id,
                         &github_project_id,
-                        &gh_project_item_id,
-                        &priority_field_id,
-                        priority_option_id,
-                    )?;
+                    )),
+                    false,
+                ),
+                _ => (None, false),
+            };
+            let mut journal = match &cli.command {
+                Command::Sync { journal, .. } => {
+                    let path = journal.clone().unwrap_or_else(|| {
+                        PathBuf::from(format!(
+                            "zenhub-sync-{}.jsonl",
+                            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+                        ))
+                    });
+                    let journal = Journal::open(&path, &github_project_id, &github_api_url)?;
+                    info!(journal = %journal.path().display(), "Recording mutations");
+                    Some(journal)
+                }
+                _ => None,
+            };
+            if let (false, Some(journal)) = (field_plan.is_empty(), journal.as_mut()) {
+                create_fields::create(
+                    github_client.clone(),
+                    &github_project_id,
+                    field_plan,
+                    journal,
+                )?;
+                github_project_fields =
+                    github::get_fields(github_client.clone(), &github_project_id)?;
+            }
+            let plan = sync::plan(
+                github_client.clone(),
+                zenhub_client,
+                &config,
+                zenhub_workspace,
+                &github_project_id,
+                &github_project_fields,
+                sync::PlanOptions {
+                    resume: checkpoint.as_ref().filter(|_| resume),
+                    fail_fast: cli.global.fail_fast,
+                },
+            )?;
+            let report_path = match &cli.command {
+                Command::Plan { report } | Command::Sync { report, .. } => report.clone(),
+                _ => None,
+            };
+            let (Some(mut checkpoint), Some(mut journal)) = (checkpoint, journal) else {
+                if let Some(path) = &report_path {
+                    plan.summary.report.write(path)?;
+                }
+                match format {
+                    OutputFormat::Text => {
+                        plan.print();
+                        plan.summary.print_failures();
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&plan.operations())?)
+                    }
+                }
+                return failed(&plan.summary);
+            };
+            let summary = sync::apply(
+                github_client,
+                &github_project_id,
+                plan,
+                &mut checkpoint,
+                &mut journal,
+                cli.global.fail_fast,
+            )?;
+            // failed issues aren't in the checkpoint, so it's kept for `--resume` to retry them
+            let kept_checkpoint = if summary.failures.is_empty() {
+                checkpoint.finish()?;
+                None
+            } else {
+                Some(checkpoint.path().to_path_buf())
+            };
+            if let Some(path) = &report_path {
+                summary.report.write(path)?;
+                info!(report = %path.display(), "Wrote the migration report");
+            }
+            match format {
+                OutputFormat::Text => {
                     println!(
-                        "Setting item {} status to {:?}",
-                        gh_project_item_id, status_option_id
+                        "Done. {} unchanged, {} added, {} updated, {} missing from GH, {} sub-issues linked, {} comments added, {} items moved, {} closed issues {}.",
+                        summary.unchanged,
+                        summary.added,
+                        summary.updated,
+                        summary.missing,
+                        summary.sub_issues,
+                        summary.comments,
+                        summary.moved,
+                        summary.closed,
+                        sync::closed_outcome(summary.closed_policy)
                     );
-                    github::set_field_option(
-                        github_client.clone(),
-                        &github_project_id,
-                        &gh_project_item_id,
-                        &status_field_id,
-                        Some(status_option_id),
-                    )?;
-                    // TODO(murph): move connected issues into sub-issues?
+                    summary.print_failures();
                 }
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
             }
+            if let Some(path) = kept_checkpoint {
+                return Err(anyhow!(
+                    "{} issue(s) failed. The checkpoint {} was kept, `sync --resume` retries them.",
+                    summary.failures.len(),
+                    path.display()
+                ));
+            }
+            return Ok(());
+        }
+        Command::Inspect { .. } | Command::Rollback { .. } => {
+            unreachable!("inspect and rollback are handled above")
         }
     }
 
     Ok(())
 }
 
-// helper to get the GH field ID for a given ZH field name, via the mappings and the results of `get_fields`
-fn zh_to_gh_field_id(
-    zh_field_name: &str,
-    field_mapping: &HashMap<&str, &str>,
-    gh_fields: &Vec<github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes>,
-) -> Result<String, Error> {
-    let gh_name = field_mapping.get(zh_field_name).ok_or_else(|| {
-        anyhow!("Couldn't find ZH name {zh_field_name} in the field mapping configuration.")
-    })?;
-    gh_fields
-        .iter()
-        .find(|gh_field| match gh_field {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2Field(f) => {
-                &&f.name == gh_name
-            }
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => &&ssf.name == gh_name,
-            _ => false,
-        })
-        .ok_or_else(|| anyhow!("Couldn't find GH mapped field {gh_name} in the GH project fields."))
-        .and_then(|f| match f {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2Field(f) => {
-                Ok(f.id.clone())
-            }
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => Ok(ssf.id.clone()),
-            _ => Err(anyhow!("Encountered something other than a field or single select field (iteration is not yet supported)!"))
-        })
+// problems from validate, verify, or preflight, `ok` is printed when there aren't any
+fn print_problems<P: fmt::Display + Serialize>(
+    problems: &[P],
+    format: OutputFormat,
+    ok: &str,
+) -> Result<(), Error> {
+    match format {
+        OutputFormat::Text if problems.is_empty() => println!("{ok}"),
+        OutputFormat::Text => problems.iter().for_each(|p| println!("{p}")),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "problems": problems }))?
+        ),
+    }
+    Ok(())
 }
 
-fn zh_to_gh_status_id(
-    zh_name: &str,
-    lane_mapping: &HashMap<&str, &str>,
-    field_mapping: &HashMap<&str, &str>,
-    gh_fields: &Vec<github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes>,
-) -> Result<String, Error> {
-    let gh_status_field = field_mapping.get("Pipeline").ok_or_else(|| {
-        anyhow!("Couldn't find ZH name {zh_name} in the field mapping configuration.")
-    })?;
-    let gh_status_name = lane_mapping.get(zh_name).ok_or_else(|| {
-        anyhow!("Couldn't find ZH name {zh_name} in the lane mapping configuration.")
-    })?;
-    gh_fields
-        .iter()
-        .find(|gh_field| match gh_field {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => &&ssf.name == gh_status_field,
-            _ => false,
-        })
-        .ok_or_else(|| {
-            anyhow!(
-                "Couldn't find GH mapped status field {gh_status_name} in the GH project fields."
-            )
-        })
-        .and_then(|f| match f {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => {
-                    let gh_status_option = ssf
-                .options
-                .iter()
-                .find(|ssf_option| &&ssf_option.name == gh_status_name).ok_or_else(|| anyhow!("Couldn't find a GH status option {gh_status_name} to match the ZH pipeline {zh_name}"))?;
-            Ok(gh_status_option.id.clone())
-        },
-            _ => Err(anyhow!(
-                "Encountered something other than a single select field for GH's status field!"
-            )),
-        })
+// the exit status for a plan or sync, non-zero when any issue failed
+fn failed(summary: &sync::Summary) -> Result<(), Error> {
+    if summary.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} issue(s) failed.", summary.failures.len()))
+    }
 }
 
-// This involves a lot of magic hardcoding for our expected situation
-fn zh_to_gh_priority(
-    priority: Option<String>,
-    field_mapping: &HashMap<&str, &str>,
-    gh_fields: &Vec<github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes>,
-) -> Result<Option<String>, Error> {
-    let gh_priorities = gh_fields
-        .iter()
-        .find(|f| match f {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => {
-                &&ssf.name
-                    == field_mapping
-                        .get("Priority")
-                        .expect("Missing GH name for 'Priority' in the field mapping.")
-            }
-            _ => false,
-        })
-        .ok_or_else(|| anyhow!("Found no GH 'Priority' field."))
-        .and_then(|v| match v {
-            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
-                ssf,
-            ) => Ok(ssf.options.clone()),
-            _ => Err(anyhow!("Found no GH options for the 'Priority' field.")),
-        })?;
-    let p = match priority {
-        Some(p) if p == "High priority".to_string() => gh_priorities.iter().find_map(|p| {
-            if p.name == "P0" {
-                Some(p.id.clone())
-            } else {
-                None
+fn inspect_workspace(zenhub_client: Client, format: OutputFormat) -> Result<(), Error> {
+    let organizations = zenhub::get_workspaces(zenhub_client, None)?;
+    match format {
+        OutputFormat::Text => {
+            for organization in organizations {
+                println!(
+                    "{} ({})",
+                    organization.name.as_deref().unwrap_or("(unnamed)"),
+                    organization.id
+                );
+                for workspace in organization.workspaces {
+                    println!(
+                        "  {} ({})",
+                        workspace.name.as_deref().unwrap_or("(unnamed)"),
+                        workspace.id
+                    );
+                    println!("    Pipelines:");
+                    for pipeline in workspace.pipelines_connection.nodes {
+                        println!("    - {} ({})", pipeline.name, pipeline.id);
+                    }
+                    println!("    Priorities:");
+                    for priority in workspace.priorities_connection.nodes {
+                        println!("    - {} ({})", priority.name, priority.id);
+                    }
+                }
             }
-        }),
-        _ => None,
-    };
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&organizations)?),
+    }
+    Ok(())
+}
 
-    Ok(p)
+fn inspect_project(
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+    format: OutputFormat,
+) -> Result<(), Error> {
+    use github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes as ProjectField;
+
+    match format {
+        OutputFormat::Text => {
+            for field in gh_fields {
+                match field {
+                    ProjectField::ProjectV2Field(f) => {
+                        println!("{} ({:?}, {})", f.name, f.data_type, f.id)
+                    }
+                    ProjectField::ProjectV2SingleSelectField(ssf) => {
+                        println!("{} (SINGLE_SELECT, {})", ssf.name, ssf.id);
+                        for option in &ssf.options {
+                            println!("  - {} ({})", option.name, option.id);
+                        }
+                    }
+                    ProjectField::ProjectV2IterationField(itf) => {
+                        println!("{} (ITERATION, {})", itf.name, itf.id);
+                        for iteration in itf
+                            .configuration
+                            .iterations
+                            .iter()
+                            .chain(itf.configuration.completed_iterations.iter())
+                        {
+                            println!(
+                                "  - {} ({}, {} days from {})",
+                                iteration.title,
+                                iteration.id,
+                                iteration.duration,
+                                iteration.start_date
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(gh_fields)?),
+    }
+    Ok(())
 }
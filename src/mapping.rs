@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, NaiveDate, TimeDelta};
+use std::collections::HashMap;
+
+use crate::{
+    config::SprintMatch, github,
+    zenhub::get_pipeline_issues::GetPipelineIssuesSearchIssuesByPipelineNodesSprintsNodes as Sprint,
+};
+
+// helper to get the GH field ID for a given ZH field name, via the mappings and the results of `get_fields`
+pub fn zh_to_gh_field_id(
+    zh_field_name: &str,
+    field_mapping: &HashMap<String, String>,
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+) -> Result<String, Error> {
+    let gh_name = field_mapping.get(zh_field_name).ok_or_else(|| {
+        anyhow!("Couldn't find ZH name {zh_field_name} in the field mapping configuration.")
+    })?;
+    gh_fields
+        .iter()
+        .find(|gh_field| match gh_field {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2Field(f) => {
+                &f.name == gh_name
+            }
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => &ssf.name == gh_name,
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2IterationField(
+                itf,
+            ) => &itf.name == gh_name,
+        })
+        .ok_or_else(|| anyhow!("Couldn't find GH mapped field {gh_name} in the GH project fields."))
+        .map(|f| match f {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2Field(f) => {
+                f.id.clone()
+            }
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => ssf.id.clone(),
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2IterationField(
+                itf,
+            ) => itf.id.clone(),
+        })
+}
+
+pub fn zh_to_gh_status_id(
+    zh_name: &str,
+    lane_mapping: &HashMap<String, String>,
+    field_mapping: &HashMap<String, String>,
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+) -> Result<String, Error> {
+    let gh_status_name = lane_mapping.get(zh_name).ok_or_else(|| {
+        anyhow!("Couldn't find ZH name {zh_name} in the lane mapping configuration.")
+    })?;
+    gh_status_option_id(gh_status_name, field_mapping, gh_fields)
+        .map_err(|e| anyhow!("{e} to match the ZH pipeline {zh_name}"))
+}
+
+// the option ID for a GH status name on the field `Pipeline` is mapped to
+pub fn gh_status_option_id(
+    gh_status_name: &str,
+    field_mapping: &HashMap<String, String>,
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+) -> Result<String, Error> {
+    let gh_status_field = field_mapping.get("Pipeline").ok_or_else(|| {
+        anyhow!("Couldn't find ZH name Pipeline in the field mapping configuration.")
+    })?;
+    gh_fields
+        .iter()
+        .find(|gh_field| match gh_field {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => &ssf.name == gh_status_field,
+            _ => false,
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Couldn't find GH mapped status field {gh_status_field} in the GH project fields."
+            )
+        })
+        .and_then(|f| match f {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => {
+                let gh_status_option = ssf
+                    .options
+                    .iter()
+                    .find(|ssf_option| ssf_option.name == gh_status_name)
+                    .ok_or_else(|| anyhow!("Couldn't find a GH status option {gh_status_name}"))?;
+                Ok(gh_status_option.id.clone())
+            }
+            _ => Err(anyhow!(
+                "Encountered something other than a single select field for GH's status field!"
+            )),
+        })
+}
+
+// `gh_priority` is the GH option name the ZH priority maps to, see `PriorityConfig::gh_option`
+pub fn zh_to_gh_priority(
+    gh_priority: Option<&String>,
+    field_mapping: &HashMap<String, String>,
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+) -> Result<Option<String>, Error> {
+    let gh_priority_field = field_mapping
+        .get("Priority")
+        .ok_or_else(|| anyhow!("Missing GH name for 'Priority' in the field mapping."))?;
+    let gh_priorities = gh_fields
+        .iter()
+        .find(|f| match f {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => &ssf.name == gh_priority_field,
+            _ => false,
+        })
+        .ok_or_else(|| anyhow!("Found no GH 'Priority' field."))
+        .and_then(|v| match v {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2SingleSelectField(
+                ssf,
+            ) => Ok(ssf.options.clone()),
+            _ => Err(anyhow!("Found no GH options for the 'Priority' field.")),
+        })?;
+    // ZH priorities without a mapping are left unset in GH
+    let p = match gh_priority {
+        Some(gh_name) => Some(
+            gh_priorities
+                .iter()
+                .find(|p| &p.name == gh_name)
+                .map(|p| p.id.clone())
+                .ok_or_else(|| anyhow!("Couldn't find a GH priority option {gh_name}."))?,
+        ),
+        None => None,
+    };
+
+    Ok(p)
+}
+
+/// Find the GH iteration for the most recent of an issue's ZH sprints. `Ok(None)` means the issue
+/// isn't in a sprint, an error means it is but there's no iteration to match it.
+pub fn zh_to_gh_iteration(
+    sprints: &[Sprint],
+    match_by: SprintMatch,
+    field_mapping: &HashMap<String, String>,
+    gh_fields: &[github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes],
+) -> Result<Option<(String, String)>, Error> {
+    // issues that carried over are in every sprint they've been through, only the latest counts
+    let Some(sprint) = sprints.iter().max_by(|a, b| a.start_at.cmp(&b.start_at)) else {
+        return Ok(None);
+    };
+    let gh_name = field_mapping.get("Sprint").ok_or_else(|| {
+        anyhow!("Couldn't find ZH name Sprint in the field mapping configuration.")
+    })?;
+    let configuration = gh_fields
+        .iter()
+        .find_map(|f| match f {
+            github::get_fields::GetFieldsNodeOnProjectV2FieldsNodes::ProjectV2IterationField(
+                itf,
+            ) if &itf.name == gh_name => Some(&itf.configuration),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            anyhow!("Couldn't find GH iteration field {gh_name} in the GH project fields.")
+        })?;
+    let sprint_name = sprint
+        .name
+        .as_ref()
+        .or(sprint.generated_name.as_ref())
+        .cloned()
+        .unwrap_or_else(|| sprint.id.clone());
+    let sprint_middle = match match_by {
+        SprintMatch::Dates => {
+            let start = DateTime::parse_from_rfc3339(&sprint.start_at)?;
+            let end = DateTime::parse_from_rfc3339(&sprint.end_at)?;
+            Some((start + (end - start) / 2).date_naive())
+        }
+        SprintMatch::Name => None,
+    };
+    configuration
+        .iterations
+        .iter()
+        .chain(configuration.completed_iterations.iter())
+        .find(|iteration| match sprint_middle {
+            Some(middle) => NaiveDate::parse_from_str(&iteration.start_date, "%Y-%m-%d")
+                .map(|start| {
+                    start <= middle && middle < start + TimeDelta::days(iteration.duration)
+                })
+                .unwrap_or(false),
+            None => iteration.title == sprint_name,
+        })
+        .map(|iteration| Some((iteration.id.clone(), iteration.title.clone())))
+        .ok_or_else(|| {
+            anyhow!(
+                "Couldn't find a GH iteration in {gh_name} to match the ZH sprint {sprint_name}"
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use github::get_fields::{
+        GetFieldsNodeOnProjectV2FieldsNodes as ProjectField,
+        GetFieldsNodeOnProjectV2FieldsNodesOnProjectV2IterationField as IterationField,
+        GetFieldsNodeOnProjectV2FieldsNodesOnProjectV2IterationFieldConfiguration as Configuration,
+        Iteration,
+    };
+
+    fn sprint(name: Option<&str>, start_at: &str, end_at: &str) -> Sprint {
+        Sprint {
+            id: format!("sprint-{start_at}"),
+            name: name.map(|n| n.to_string()),
+            generated_name: Some(format!("Sprint from {start_at}")),
+            start_at: start_at.to_string(),
+            end_at: end_at.to_string(),
+        }
+    }
+
+    fn iteration(title: &str, start_date: &str, duration: i64) -> Iteration {
+        Iteration {
+            id: format!("iteration-{title}"),
+            title: title.to_string(),
+            start_date: start_date.to_string(),
+            duration,
+        }
+    }
+
+    // a Sprint iteration field with two upcoming iterations and a completed one
+    fn gh_fields() -> Vec<ProjectField> {
+        vec![ProjectField::ProjectV2IterationField(IterationField {
+            id: "iterations".to_string(),
+            name: "Sprint".to_string(),
+            configuration: Configuration {
+                iterations: vec![
+                    iteration("Iteration 2", "2024-03-15", 14),
+                    iteration("Iteration 3", "2024-03-29", 14),
+                ],
+                completed_iterations: vec![iteration("Iteration 1", "2024-03-01", 14)],
+            },
+        })]
+    }
+
+    fn mapping() -> HashMap<String, String> {
+        HashMap::from([("Sprint".to_string(), "Sprint".to_string())])
+    }
+
+    fn matched(sprints: &[Sprint], match_by: SprintMatch) -> Option<String> {
+        zh_to_gh_iteration(sprints, match_by, &mapping(), &gh_fields())
+            .unwrap()
+            .map(|(_, title)| title)
+    }
+
+    #[test]
+    fn iteration_by_dates_contains_the_middle_of_the_sprint() {
+        // the sprint is offset from the iterations, its middle (Mar 16th) decides
+        let sprints = [sprint(None, "2024-03-10T00:00:00Z", "2024-03-23T00:00:00Z")];
+        assert_eq!(
+            matched(&sprints, SprintMatch::Dates).as_deref(),
+            Some("Iteration 2")
+        );
+        // iterations end the day before the next one starts
+        let sprints = [sprint(None, "2024-03-14T00:00:00Z", "2024-03-14T12:00:00Z")];
+        assert_eq!(
+            matched(&sprints, SprintMatch::Dates).as_deref(),
+            Some("Iteration 1")
+        );
+    }
+
+    #[test]
+    fn iteration_uses_the_latest_sprint() {
+        let sprints = [
+            sprint(None, "2024-03-29T00:00:00Z", "2024-04-11T00:00:00Z"),
+            sprint(None, "2024-03-01T00:00:00Z", "2024-03-14T00:00:00Z"),
+        ];
+        assert_eq!(
+            matched(&sprints, SprintMatch::Dates).as_deref(),
+            Some("Iteration 3")
+        );
+        assert_eq!(matched(&[], SprintMatch::Dates), None);
+    }
+
+    #[test]
+    fn iteration_by_dates_fails_outside_every_iteration() {
+        let sprints = [sprint(
+            Some("Iteration 2"),
+            "2024-05-01T00:00:00Z",
+            "2024-05-14T00:00:00Z",
+        )];
+        assert!(
+            zh_to_gh_iteration(&sprints, SprintMatch::Dates, &mapping(), &gh_fields()).is_err()
+        );
+    }
+
+    #[test]
+    fn iteration_by_name_matches_the_title() {
+        // dates don't matter when matching by name
+        let sprints = [sprint(
+            Some("Iteration 3"),
+            "2024-05-01T00:00:00Z",
+            "2024-05-14T00:00:00Z",
+        )];
+        assert_eq!(
+            matched(&sprints, SprintMatch::Name).as_deref(),
+            Some("Iteration 3")
+        );
+        // unnamed sprints go by their generated name
+        let sprints = [sprint(None, "2024-03-01T00:00:00Z", "2024-03-14T00:00:00Z")];
+        assert!(zh_to_gh_iteration(&sprints, SprintMatch::Name, &mapping(), &gh_fields()).is_err());
+    }
+}
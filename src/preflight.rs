@@ -0,0 +1,58 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use tracing::{info, warn};
+
+use crate::{
+    config::Config,
+    github::{
+        self, get_fields::GetFieldsNodeOnProjectV2FieldsNodes as ProjectField, RepositoryAccess,
+    },
+    validate,
+    zenhub::{self, Workspace},
+};
+
+/// Everything a sync needs to be able to do, checked before it changes anything: the config against
+/// the live fields and options, access to every repository in the workspace (enough to manage issues
+/// when sub-issues or blocking comments are on), and write access to the project. Returns every
+/// problem found rather than stopping at the first one.
+pub fn preflight(
+    github_client: Client,
+    zenhub_client: Client,
+    config: &Config,
+    workspace: &Workspace,
+    project_id: &str,
+    gh_fields: &[ProjectField],
+) -> Result<Vec<String>, Error> {
+    let mut problems = validate::validate(config, workspace, gh_fields);
+
+    // linking sub-issues and commenting need more than read access to the issues
+    let writes_issues = config.epics.sub_issues || config.blocking.comment;
+    info!("Checking the GitHub token can access every workspace repository");
+    for repository in zenhub::get_workspace_repositories(zenhub_client, &workspace.id)? {
+        let owner = &repository.owner.login;
+        let name = &repository.name;
+        match github::repository_access(github_client.clone(), owner, name)? {
+            RepositoryAccess::None => problems.push(format!(
+                "GH token can't read the repository {owner}/{name}, so its issues can't be synced."
+            )),
+            RepositoryAccess::Read if writes_issues => problems.push(format!(
+                "GH token can't manage issues in the repository {owner}/{name}, which linking sub-issues and blocking comments need (Issues:RW)."
+            )),
+            RepositoryAccess::Unknown if writes_issues => warn!(
+                repository = %format_args!("{owner}/{name}"),
+                "GH didn't say whether the token can manage issues in the repository, sub-issues and blocking comments might fail"
+            ),
+            _ => {}
+        }
+    }
+
+    info!("Checking the GitHub token can update the project");
+    if !github::can_update_project(github_client, project_id)? {
+        problems.push(
+            "GH token can't update the project, it needs write access to it (Projects:RW)."
+                .to_string(),
+        );
+    }
+
+    Ok(problems)
+}
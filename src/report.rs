@@ -0,0 +1,146 @@
+use anyhow::{Context, Error};
+use serde::Serialize;
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::sync::IssueKey;
+
+/// One row per ZH issue a plan or sync looked at, so what moved can be checked issue by issue.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub issues: Vec<ReportRow>,
+    // issue -> index in `issues`
+    #[serde(skip)]
+    index: HashMap<IssueKey, usize>,
+}
+
+// flat so it can be written as CSV too
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportRow {
+    // owner/repo
+    pub repo: String,
+    pub number: i64,
+    pub url: String,
+    pub pipeline: String,
+    // GH status the issue is synced to
+    pub status: Option<String>,
+    pub estimate: Option<f64>,
+    // GH priority the issue is synced to
+    pub priority: Option<String>,
+    pub item_id: Option<String>,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Added,
+    Updated,
+    Unchanged,
+    // in ZH but not GH
+    Missing,
+    Failed,
+    // closed, and the closed policy is to skip them
+    Skipped,
+}
+
+/// Counts for one ZH pipeline, to reconcile against the issue counts ZH and GH show.
+#[derive(Debug, Default, Serialize)]
+pub struct PipelineTotals {
+    pub pipeline: String,
+    pub issues: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub missing: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Report {
+    pub fn push(&mut self, issue: &IssueKey, pipeline: &str, url: &str, outcome: Outcome) {
+        self.index.insert(issue.clone(), self.issues.len());
+        self.issues.push(ReportRow {
+            repo: format!("{}/{}", issue.owner, issue.repo),
+            number: issue.number,
+            url: url.to_string(),
+            pipeline: pipeline.to_string(),
+            status: None,
+            estimate: None,
+            priority: None,
+            item_id: None,
+            outcome,
+            error: None,
+        });
+    }
+
+    pub fn get_mut(&mut self, issue: &IssueKey) -> Option<&mut ReportRow> {
+        self.index.get(issue).map(|&i| &mut self.issues[i])
+    }
+
+    pub fn fail(&mut self, issue: &IssueKey, error: &str) {
+        if let Some(row) = self.get_mut(issue) {
+            row.outcome = Outcome::Failed;
+            row.error = Some(error.to_string());
+        }
+    }
+
+    /// Totals per pipeline, in the order the pipelines were synced.
+    pub fn pipelines(&self) -> Vec<PipelineTotals> {
+        let mut totals: Vec<PipelineTotals> = vec![];
+        for row in &self.issues {
+            let i = match totals.iter().position(|t| t.pipeline == row.pipeline) {
+                Some(i) => i,
+                None => {
+                    totals.push(PipelineTotals {
+                        pipeline: row.pipeline.clone(),
+                        ..Default::default()
+                    });
+                    totals.len() - 1
+                }
+            };
+            let total = &mut totals[i];
+            total.issues += 1;
+            *match row.outcome {
+                Outcome::Added => &mut total.added,
+                Outcome::Updated => &mut total.updated,
+                Outcome::Unchanged => &mut total.unchanged,
+                Outcome::Missing => &mut total.missing,
+                Outcome::Failed => &mut total.failed,
+                Outcome::Skipped => &mut total.skipped,
+            } += 1;
+        }
+        totals
+    }
+
+    /// Write the report as CSV if the path ends in `.csv`, otherwise as JSON. CSV can only hold one
+    /// table, so the pipeline totals go next to it in `<name>-pipelines.csv`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let context = || format!("Couldn't write report file {}.", path.display());
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+        {
+            write_csv(path, &self.issues).with_context(context)?;
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let totals_path = path.with_file_name(format!("{stem}-pipelines.csv"));
+            write_csv(&totals_path, &self.pipelines())
+                .with_context(|| format!("Couldn't write report file {}.", totals_path.display()))
+        } else {
+            let json = serde_json::json!({
+                "issues": self.issues,
+                "pipelines": self.pipelines(),
+            });
+            fs::write(path, serde_json::to_vec_pretty(&json)?).with_context(context)
+        }
+    }
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,508 @@
+use graphql_client::GraphQLQuery;
+use reqwest::{
+    blocking::{Client, Response as HttpResponse},
+    header::HeaderMap,
+    IntoUrl, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{trace, warn};
+
+use crate::error::ApiError;
+
+const MAX_ATTEMPTS: u32 = 6;
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// GH's primary limit is 5,000 points an hour, stop a little short so other tools using the same token
+// aren't starved either
+const LOW_RATE_LIMIT: u64 = 50;
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+// mutations that would happen twice if a request that timed out or got a 5xx had actually been
+// applied, so those failures are returned instead of retried. Everything else is safe to resend.
+const NOT_IDEMPOTENT: [&str; 3] = ["AddComment", "AddSubIssue", "CreateField"];
+
+/// Drop in replacement for graphql_client's `post_graphql_blocking` that every API call goes through.
+/// Transient failures (timeouts, 5xx, rate limits) are retried with exponential backoff and jitter,
+/// waiting as long as `Retry-After`/`X-RateLimit-Reset` ask when they're sent. Timeouts and 5xx
+/// aren't retried for the few mutations that aren't safe to make twice. When a response says the
+/// rate limit budget is nearly spent, this sleeps until it resets rather than failing partway
+/// through a board. Any GraphQL `errors` in the response fail the call, with the server's messages.
+pub fn post_graphql<Q: GraphQLQuery, U: IntoUrl>(
+    client: &Client,
+    url: U,
+    variables: Q::Variables,
+) -> Result<Q::ResponseData, ApiError> {
+    post_graphql_with_token::<Q, U>(client, url, variables, None)
+}
+
+/// Hands out the bearer token for a request, for clients whose token can expire partway through a run.
+pub type TokenSource<'a> = &'a dyn Fn() -> Result<String, ApiError>;
+
+/// `post_graphql` for clients without a fixed Authorization header. The token is asked for on every
+/// attempt, so retries after a long wait don't go out with an expired one.
+pub fn post_graphql_with_token<Q: GraphQLQuery, U: IntoUrl>(
+    client: &Client,
+    url: U,
+    variables: Q::Variables,
+    token: Option<TokenSource>,
+) -> Result<Q::ResponseData, ApiError> {
+    let query = Q::build_query(variables);
+    let operation = query.operation_name;
+    let url = url
+        .into_url()
+        .map_err(|e| ApiError::transport(operation, e))?;
+    // serialize once so the same body can be resent
+    let body = serde_json::to_vec(&query).map_err(|e| ApiError::transport(operation, e))?;
+    trace!(
+        operation,
+        body = %String::from_utf8_lossy(&body),
+        "Sending request"
+    );
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut request = client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(token) = token {
+            request = request.bearer_auth(token()?);
+        }
+        let result = request.send();
+        let (error, wait, maybe_applied) = match result {
+            Ok(response) => match check_response::<Q::ResponseData>(operation, response)? {
+                Outcome::Done(data) => return Ok(data),
+                Outcome::Retry {
+                    error,
+                    wait,
+                    maybe_applied,
+                } => (error, wait, maybe_applied),
+            },
+            // a request that couldn't connect never reached the server
+            Err(e) if e.is_connect() => (ApiError::transport(operation, e), None, false),
+            Err(e) if e.is_timeout() || e.is_request() => {
+                (ApiError::transport(operation, e), None, true)
+            }
+            Err(e) => return Err(ApiError::transport(operation, e)),
+        };
+        if gives_up(operation, attempt, maybe_applied) {
+            return Err(error);
+        }
+        let wait = wait.unwrap_or_else(|| backoff(attempt));
+        warn!(
+            host = url.host_str().unwrap_or_default(),
+            operation,
+            wait_secs = wait.as_secs(),
+            attempt = attempt + 1,
+            %error,
+            "Retrying request"
+        );
+        thread::sleep(wait);
+    }
+}
+
+// whether a failed attempt is the last one, either because they've run out or because the mutation
+// might have gone through and can't safely be sent again
+fn gives_up(operation: &str, attempt: u32, maybe_applied: bool) -> bool {
+    attempt >= MAX_ATTEMPTS || (maybe_applied && NOT_IDEMPOTENT.contains(&operation))
+}
+
+enum Outcome<T> {
+    Done(T),
+    Retry {
+        // what to fail with if this was the last attempt
+        error: ApiError,
+        // how long the server asked us to wait, if it did
+        wait: Option<Duration>,
+        // whether the server might have made the change anyway, i.e. a 5xx rather than a rate limit
+        maybe_applied: bool,
+    },
+}
+
+// graphql_client's error type drops GH's top level `type`, which is the best way to tell errors apart
+#[derive(Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<GraphqlError>>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: Option<String>,
+    extensions: Option<HashMap<String, Value>>,
+}
+
+impl GraphqlError {
+    // GH sends `type`, others tend to put a `code` in the extensions
+    fn code(&self) -> String {
+        self.error_type
+            .as_deref()
+            .or_else(|| {
+                let extensions = self.extensions.as_ref()?;
+                extensions
+                    .get("type")
+                    .or_else(|| extensions.get("code"))?
+                    .as_str()
+            })
+            .unwrap_or_default()
+            .to_uppercase()
+    }
+
+    fn is_rate_limit(&self) -> bool {
+        self.code() == "RATE_LIMITED" || self.message.to_lowercase().contains("rate limit")
+    }
+}
+
+fn check_response<T: DeserializeOwned>(
+    operation: &str,
+    response: HttpResponse,
+) -> Result<Outcome<T>, ApiError> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::FORBIDDEN {
+        let message = http_message(status, response);
+        let rate_limited = is_rate_limited(status, &headers, &message);
+        let operation = operation.to_string();
+        let messages = vec![message];
+        if rate_limited {
+            return Ok(Outcome::Retry {
+                error: ApiError::RateLimited {
+                    operation,
+                    messages,
+                },
+                wait: Some(rate_limit_wait(&headers)),
+                maybe_applied: false,
+            });
+        }
+        return Err(ApiError::PermissionDenied {
+            operation,
+            messages,
+        });
+    }
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        return Ok(Outcome::Retry {
+            error: ApiError::transport(operation, http_message(status, response)),
+            wait: server_wait(&headers),
+            maybe_applied: true,
+        });
+    }
+    if !status.is_success() {
+        let messages = vec![http_message(status, response)];
+        let operation = operation.to_string();
+        return Err(match status {
+            StatusCode::UNAUTHORIZED => ApiError::PermissionDenied {
+                operation,
+                messages,
+            },
+            StatusCode::NOT_FOUND => ApiError::NotFound {
+                operation,
+                messages,
+            },
+            s if s.is_client_error() => ApiError::Validation {
+                operation,
+                messages,
+            },
+            _ => ApiError::Transport {
+                operation,
+                messages,
+            },
+        });
+    }
+    let body = response
+        .text()
+        .map_err(|e| ApiError::transport(operation, e))?;
+    trace!(operation, body, "Got response");
+    let response: GraphqlResponse<T> =
+        serde_json::from_str(&body).map_err(|e| ApiError::transport(operation, e))?;
+    let errors = response.errors.unwrap_or_default();
+    // GH reports running out of points as a 200 with a RATE_LIMITED error
+    if errors.iter().any(GraphqlError::is_rate_limit) {
+        return Ok(Outcome::Retry {
+            error: ApiError::RateLimited {
+                operation: operation.to_string(),
+                messages: errors.into_iter().map(|e| e.message).collect(),
+            },
+            wait: Some(rate_limit_wait(&headers)),
+            maybe_applied: false,
+        });
+    }
+    if let Some(first) = errors.first() {
+        let code = first.code();
+        let messages = errors.iter().map(|e| e.message.clone()).collect();
+        let operation = operation.to_string();
+        return Err(match code.as_str() {
+            "NOT_FOUND" => ApiError::NotFound {
+                operation,
+                messages,
+            },
+            "FORBIDDEN" | "INSUFFICIENT_SCOPES" | "UNAUTHORIZED" | "UNAUTHENTICATED" => {
+                ApiError::PermissionDenied {
+                    operation,
+                    messages,
+                }
+            }
+            _ => ApiError::Validation {
+                operation,
+                messages,
+            },
+        });
+    }
+    if let (Some(left), Some(wait)) = (remaining(&headers), reset_wait(&headers)) {
+        if left < LOW_RATE_LIMIT {
+            warn!(
+                remaining = left,
+                wait_secs = wait.as_secs(),
+                "Rate limit almost used up, waiting for it to reset"
+            );
+            thread::sleep(wait);
+        }
+    }
+    response
+        .data
+        .map(Outcome::Done)
+        .ok_or_else(|| ApiError::transport(operation, "Response had no data and no errors."))
+}
+
+// Whether a 403 or 429 is a rate limit rather than a permissions problem. GH's secondary rate limits
+// can be a 403 where only the message tells them apart, there's no Retry-After and the primary limit
+// isn't spent.
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap, message: &str) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || headers.contains_key(reqwest::header::RETRY_AFTER)
+        || remaining(headers) == Some(0)
+        || message.to_lowercase().contains("secondary rate limit")
+}
+
+// the `message` from an error body if it's JSON (GH's are), otherwise the status and raw body
+pub fn http_message(status: StatusCode, response: HttpResponse) -> String {
+    let body = response.text().unwrap_or_default();
+    serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(|m| m.to_string()))
+        .map(|message| format!("{status}: {message}"))
+        .unwrap_or_else(|| format!("{status}: {}", body.trim()))
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn remaining(headers: &HeaderMap) -> Option<u64> {
+    header_u64(headers, "x-ratelimit-remaining")
+}
+
+// `X-RateLimit-Reset` is a unix timestamp, a second is added to stay clear of clock skew
+fn reset_wait(headers: &HeaderMap) -> Option<Duration> {
+    let reset = header_u64(headers, "x-ratelimit-reset")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+// `Retry-After` wins since it's the more specific of the two. Only the seconds form is supported, GH
+// and ZH don't send dates.
+fn server_wait(headers: &HeaderMap) -> Option<Duration> {
+    header_u64(headers, reqwest::header::RETRY_AFTER.as_str())
+        .map(Duration::from_secs)
+        .or_else(|| reset_wait(headers))
+}
+
+// How long GH's docs say to wait once rate limited: `Retry-After` if it's sent, then until the reset
+// if the primary limit is spent, and otherwise at least a minute for a secondary limit. The reset
+// time is sent either way, but it's for the primary limit so it's no use for secondary ones.
+fn rate_limit_wait(headers: &HeaderMap) -> Duration {
+    match header_u64(headers, reqwest::header::RETRY_AFTER.as_str()) {
+        Some(secs) => Duration::from_secs(secs),
+        None if remaining(headers) == Some(0) => {
+            reset_wait(headers).unwrap_or(SECONDARY_RATE_LIMIT_WAIT)
+        }
+        None => SECONDARY_RATE_LIMIT_WAIT,
+    }
+}
+
+// exponential backoff with full jitter, so parallel runs don't retry in lockstep
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_BACKOFF);
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (ceiling.as_millis() as u64 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    reqwest::header::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn forbidden_is_only_rate_limited_when_it_says_so() {
+        let forbidden = StatusCode::FORBIDDEN;
+        assert!(!is_rate_limited(
+            forbidden,
+            &HeaderMap::new(),
+            "403 Forbidden: Resource not accessible by integration"
+        ));
+        assert!(!is_rate_limited(
+            forbidden,
+            &headers(&[("x-ratelimit-remaining", "4000".to_string())]),
+            "403 Forbidden: Must have admin rights to Repository."
+        ));
+        assert!(is_rate_limited(
+            forbidden,
+            &HeaderMap::new(),
+            "403 Forbidden: You have exceeded a secondary rate limit. Please wait a few minutes."
+        ));
+        assert!(is_rate_limited(
+            forbidden,
+            &headers(&[("retry-after", "30".to_string())]),
+            "403 Forbidden"
+        ));
+        assert!(is_rate_limited(
+            forbidden,
+            &headers(&[("x-ratelimit-remaining", "0".to_string())]),
+            "403 Forbidden: API rate limit exceeded"
+        ));
+        assert!(is_rate_limited(
+            StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new(),
+            "429 Too Many Requests"
+        ));
+    }
+
+    #[test]
+    fn rate_limit_wait_prefers_retry_after() {
+        let reset = (now() + 600).to_string();
+        assert_eq!(
+            rate_limit_wait(&headers(&[
+                ("retry-after", "30".to_string()),
+                ("x-ratelimit-remaining", "0".to_string()),
+                ("x-ratelimit-reset", reset),
+            ])),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn rate_limit_wait_waits_for_the_reset_once_the_primary_limit_is_spent() {
+        let wait = rate_limit_wait(&headers(&[
+            ("x-ratelimit-remaining", "0".to_string()),
+            ("x-ratelimit-reset", (now() + 120).to_string()),
+        ]));
+        assert!(
+            (Duration::from_secs(120)..=Duration::from_secs(121)).contains(&wait),
+            "{wait:?}"
+        );
+        // a reset that's already passed only waits out the clock skew
+        assert_eq!(
+            rate_limit_wait(&headers(&[
+                ("x-ratelimit-remaining", "0".to_string()),
+                ("x-ratelimit-reset", (now() - 10).to_string()),
+            ])),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn rate_limit_wait_ignores_the_reset_for_secondary_limits() {
+        assert_eq!(
+            rate_limit_wait(&headers(&[
+                ("x-ratelimit-remaining", "4000".to_string()),
+                ("x-ratelimit-reset", (now() + 3000).to_string()),
+            ])),
+            SECONDARY_RATE_LIMIT_WAIT
+        );
+        assert_eq!(
+            rate_limit_wait(&HeaderMap::new()),
+            SECONDARY_RATE_LIMIT_WAIT
+        );
+        assert_eq!(
+            rate_limit_wait(&headers(&[("retry-after", "a date".to_string())])),
+            SECONDARY_RATE_LIMIT_WAIT
+        );
+    }
+
+    #[test]
+    fn server_wait_uses_whatever_was_sent() {
+        assert_eq!(
+            server_wait(&headers(&[
+                ("retry-after", " 5 ".to_string()),
+                ("x-ratelimit-reset", (now() + 600).to_string()),
+            ])),
+            Some(Duration::from_secs(5))
+        );
+        let wait =
+            server_wait(&headers(&[("x-ratelimit-reset", (now() + 60).to_string())])).unwrap();
+        assert!(
+            (Duration::from_secs(60)..=Duration::from_secs(61)).contains(&wait),
+            "{wait:?}"
+        );
+        assert_eq!(server_wait(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn backoff_stays_under_its_ceiling() {
+        for attempt in 1..=MAX_ATTEMPTS + 4 {
+            let ceiling = BASE_BACKOFF
+                .saturating_mul(2u32.pow(attempt - 1))
+                .min(MAX_BACKOFF);
+            for _ in 0..20 {
+                assert!(backoff(attempt) <= ceiling, "attempt {attempt}");
+            }
+        }
+    }
+
+    #[test]
+    fn graphql_errors_are_rate_limits_by_type_or_message() {
+        let error = |json: Value| serde_json::from_value::<GraphqlError>(json).unwrap();
+        assert!(
+            error(serde_json::json!({ "type": "RATE_LIMITED", "message": "" })).is_rate_limit()
+        );
+        assert!(error(serde_json::json!({
+            "message": "API rate limit exceeded for user ID 1.",
+        }))
+        .is_rate_limit());
+        let not_found = error(serde_json::json!({
+            "message": "Could not resolve to a node",
+            "extensions": { "code": "not_found" },
+        }));
+        assert!(!not_found.is_rate_limit());
+        assert_eq!(not_found.code(), "NOT_FOUND");
+    }
+
+    #[test]
+    fn gives_up_on_ambiguous_failures_of_non_idempotent_mutations() {
+        assert!(!gives_up("SetFieldValue", 1, true));
+        assert!(!gives_up("AddComment", 1, false));
+        for operation in NOT_IDEMPOTENT {
+            assert!(gives_up(operation, 1, true), "{operation}");
+        }
+        assert!(gives_up("SetFieldValue", MAX_ATTEMPTS, false));
+        assert!(!gives_up("SetFieldValue", MAX_ATTEMPTS - 1, false));
+    }
+}
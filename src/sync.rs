@@ -0,0 +1,1656 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, iter, mem,
+};
+use tracing::{debug, field, info, info_span, warn, Span};
+
+use crate::github::{
+    self,
+    get_fields::GetFieldsNodeOnProjectV2FieldsNodes as ProjectField,
+    get_project_items::{
+        GetProjectItemsNodeOnProjectV2ItemsNodes as ProjectItemNode,
+        GetProjectItemsNodeOnProjectV2ItemsNodesContent as ItemContent,
+        GetProjectItemsNodeOnProjectV2ItemsNodesFieldValuesNodes as ItemFieldValue, ItemFieldId,
+    },
+};
+use crate::{
+    checkpoint::{Checkpoint, ResumePoint, SyncedIssue},
+    config::{ClosedPolicy, Config},
+    error::ApiError,
+    journal::{Entry, Journal},
+    mapping::{
+        gh_status_option_id, zh_to_gh_field_id, zh_to_gh_iteration, zh_to_gh_priority,
+        zh_to_gh_status_id,
+    },
+    report::{Outcome, Report},
+    zenhub::{
+        self,
+        get_pipeline_issues::{
+            GetPipelineIssuesSearchIssuesByPipelineNodesParentEpicsNodes as ParentEpic, IssueRef,
+            IssueState,
+        },
+        PagedIssue,
+    },
+};
+
+/// The owner/repo#number an issue or PR is known by on both ZH and GH.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IssueKey {
+    pub owner: String,
+    pub repo: String,
+    pub number: i64,
+}
+
+impl fmt::Display for IssueKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}#{}", self.owner, self.repo, self.number)
+    }
+}
+
+/// A GH project field value. `None` means the field is (or should be) empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum FieldValue {
+    Number(Option<f64>),
+    // holds the option ID, not its name
+    SingleSelect(Option<String>),
+    Text(Option<String>),
+    // holds the iteration ID
+    Iteration(Option<String>),
+}
+
+impl FieldValue {
+    /// The empty value of the same type.
+    pub fn cleared(&self) -> FieldValue {
+        match self {
+            FieldValue::Number(_) => FieldValue::Number(None),
+            FieldValue::SingleSelect(_) => FieldValue::SingleSelect(None),
+            FieldValue::Text(_) => FieldValue::Text(None),
+            FieldValue::Iteration(_) => FieldValue::Iteration(None),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            FieldValue::Number(v) => v.is_none(),
+            FieldValue::SingleSelect(v) | FieldValue::Text(v) | FieldValue::Iteration(v) => {
+                v.is_none()
+            }
+        }
+    }
+}
+
+/// The parts of a GH issue/PR we need, whether or not it's in the project yet.
+#[derive(Debug, Clone)]
+pub struct GhIssue {
+    // Issue/PR node ID, not a project item ID
+    pub id: String,
+    // only issues can have a parent (or be one)
+    pub is_pull_request: bool,
+    pub parent_id: Option<String>,
+}
+
+/// An item that's already in the GH project, along with the field values it currently has.
+#[derive(Debug, Clone)]
+pub struct ProjectItem {
+    pub item_id: String,
+    // index in the project's current item order
+    pub position: usize,
+    pub content: GhIssue,
+    // archived items are still returned with the rest, they're only hidden in GH's views
+    pub archived: bool,
+    // field ID -> value, fields without a value aren't present
+    pub field_values: HashMap<String, FieldValue>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub unchanged: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub missing: usize,
+    pub sub_issues: usize,
+    pub comments: usize,
+    pub moved: usize,
+    pub closed: usize,
+    pub closed_policy: ClosedPolicy,
+    pub failures: Vec<Failure>,
+    // written separately with `--report`, it's too long for the summary
+    #[serde(skip)]
+    pub report: Report,
+}
+
+/// Something that went wrong with one issue, the rest of the run carries on without it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    pub issue: IssueKey,
+    // what was being done, i.e. "map the status"
+    pub step: String,
+    pub error: String,
+}
+
+impl Summary {
+    /// Note that an issue failed and keep going, or with `fail_fast` end the run with the error.
+    pub fn record_failure(
+        &mut self,
+        fail_fast: bool,
+        issue: &IssueKey,
+        step: &str,
+        error: Error,
+    ) -> Result<(), Error> {
+        if fail_fast {
+            return Err(error.context(format!("Couldn't {step} for {issue}.")));
+        }
+        warn!(%issue, step, %error, "Failed, carrying on");
+        self.report.fail(issue, &error.to_string());
+        self.failures.push(Failure {
+            issue: issue.clone(),
+            step: step.to_string(),
+            error: error.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Failures grouped by the step they happened in.
+    pub fn print_failures(&self) {
+        let mut by_step: BTreeMap<&str, Vec<&Failure>> = BTreeMap::new();
+        for failure in &self.failures {
+            by_step.entry(&failure.step).or_default().push(failure);
+        }
+        for (step, failures) in by_step {
+            println!("Couldn't {step} ({}):", failures.len());
+            for failure in failures {
+                println!("  {} {}", failure.issue, failure.error);
+            }
+        }
+    }
+}
+
+/// A single field we need to set on an item.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field_id: String,
+    pub field_name: String,
+    pub value: FieldValue,
+    // human readable version of `value`, i.e. the option name instead of its ID
+    pub label: String,
+    // what the item had before, so the change can be rolled back
+    pub previous: FieldValue,
+}
+
+impl FieldChange {
+    // `previous` starts out empty, `changed_fields` fills it in from the existing item
+    pub fn new(
+        field_id: String,
+        field_name: String,
+        value: FieldValue,
+        label: String,
+    ) -> FieldChange {
+        FieldChange {
+            field_id,
+            field_name,
+            previous: value.cleared(),
+            value,
+            label,
+        }
+    }
+}
+
+/// Everything that needs to happen to one ZH issue to make GH match it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemPlan {
+    pub issue: IssueKey,
+    pub pipeline: String,
+    // GH project item ID, only known up front if the item is already in the project
+    pub item_id: Option<String>,
+    // Issue/PR node ID that needs to be added to the project, if it isn't there yet
+    pub add_content_id: Option<String>,
+    pub changes: Vec<FieldChange>,
+    pub sub_issue: Option<SubIssueLink>,
+    pub comment: Option<PlannedComment>,
+    // closed issue to archive once its fields are set
+    pub archive: bool,
+    // where the issue was read from in ZH, so a checkpoint can point back there
+    pub pipeline_id: String,
+    pub page: usize,
+    pub cursor: Option<String>,
+    // issues since the last planned one that didn't need anything, checkpointed along with this one
+    #[serde(skip)]
+    pub unchanged_before: Vec<UnchangedIssue>,
+}
+
+/// An issue that's already in sync, and where it was read from in ZH, since it has to be read again
+/// if a run dies before it's checkpointed.
+#[derive(Debug, Clone)]
+pub struct UnchangedIssue {
+    pub synced: SyncedIssue,
+    pub pipeline_id: String,
+    pub page: usize,
+    pub cursor: Option<String>,
+}
+
+/// A ZH epic -> child issue relationship that needs to become a GH sub-issue.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubIssueLink {
+    pub parent: IssueKey,
+    pub child: IssueKey,
+    pub parent_id: String,
+    pub child_id: String,
+}
+
+/// An item that's out of ZH order and needs to go straight after another one. Item IDs are only
+/// known up front for items that are already in the project.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemMove {
+    pub issue: IssueKey,
+    pub pipeline: String,
+    pub item_id: Option<String>,
+    // `None` means the top of the project
+    pub after: Option<IssueKey>,
+    pub after_item_id: Option<String>,
+}
+
+/// A comment to leave on a GH issue/PR.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedComment {
+    pub issue: IssueKey,
+    pub subject_id: String,
+    pub body: String,
+    // the Blocking field, which records that the comment was made
+    pub field_id: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub items: Vec<ItemPlan>,
+    pub moves: Vec<ItemMove>,
+    pub summary: Summary,
+}
+
+/// One mutation the plan would make, flattened out for display.
+#[derive(Debug, Serialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum Operation<'a> {
+    AddItem {
+        issue: &'a IssueKey,
+        content_id: &'a str,
+    },
+    SetField {
+        issue: &'a IssueKey,
+        item_id: Option<&'a str>,
+        #[serde(flatten)]
+        change: &'a FieldChange,
+    },
+    AddSubIssue {
+        parent: &'a IssueKey,
+        child: &'a IssueKey,
+    },
+    AddComment {
+        issue: &'a IssueKey,
+        body: &'a str,
+    },
+    MoveItem {
+        issue: &'a IssueKey,
+        after: Option<&'a IssueKey>,
+    },
+    ArchiveItem {
+        issue: &'a IssueKey,
+    },
+}
+
+impl Plan {
+    pub fn operations(&self) -> Vec<Operation<'_>> {
+        self.items
+            .iter()
+            .flat_map(|item| {
+                let add = item
+                    .add_content_id
+                    .as_deref()
+                    .map(|content_id| Operation::AddItem {
+                        issue: &item.issue,
+                        content_id,
+                    });
+                let sets = item.changes.iter().map(|change| Operation::SetField {
+                    issue: &item.issue,
+                    item_id: item.item_id.as_deref(),
+                    change,
+                });
+                let sub_issue = item.sub_issue.iter().map(|link| Operation::AddSubIssue {
+                    parent: &link.parent,
+                    child: &link.child,
+                });
+                let comment = item.comment.iter().map(|comment| Operation::AddComment {
+                    issue: &comment.issue,
+                    body: &comment.body,
+                });
+                let archive = item
+                    .archive
+                    .then_some(Operation::ArchiveItem { issue: &item.issue });
+                add.into_iter()
+                    .chain(sets)
+                    .chain(sub_issue)
+                    .chain(comment)
+                    .chain(archive)
+            })
+            .chain(self.moves.iter().map(|m| Operation::MoveItem {
+                issue: &m.issue,
+                after: m.after.as_ref(),
+            }))
+            .collect()
+    }
+
+    pub fn print(&self) {
+        for operation in self.operations() {
+            match operation {
+                Operation::AddItem { issue, content_id } => {
+                    println!("add {issue} to the project (GH item ID {content_id})")
+                }
+                Operation::SetField { issue, change, .. } => {
+                    println!("set {issue} {} to {}", change.field_name, change.label)
+                }
+                Operation::AddSubIssue { parent, child } => {
+                    println!("make {child} a sub-issue of {parent}")
+                }
+                Operation::AddComment { issue, body } => {
+                    println!("comment on {issue}: {body}")
+                }
+                Operation::MoveItem {
+                    issue,
+                    after: Some(after),
+                } => println!("move {issue} after {after}"),
+                Operation::MoveItem { issue, after: None } => {
+                    println!("move {issue} to the top of the project")
+                }
+                Operation::ArchiveItem { issue } => println!("archive {issue}"),
+            }
+        }
+        println!(
+            "Plan: {} to add, {} to update, {} unchanged, {} missing from GH, {} sub-issues to link, {} comments to add, {} items to move, {} closed issues {}.",
+            self.summary.added,
+            self.summary.updated,
+            self.summary.unchanged,
+            self.summary.missing,
+            self.summary.sub_issues,
+            self.summary.comments,
+            self.summary.moved,
+            self.summary.closed,
+            closed_outcome(self.summary.closed_policy)
+        );
+    }
+}
+
+/// Index the results of `get_project_items` by the issue/PR they point to. Draft issues are skipped
+/// since they can't have come from ZH.
+pub fn index_project_items(items: Vec<ProjectItemNode>) -> HashMap<IssueKey, ProjectItem> {
+    items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, item)| {
+            let (key, content) = match item.content? {
+                ItemContent::Issue(i) => (
+                    IssueKey {
+                        owner: i.repository.owner.login,
+                        repo: i.repository.name,
+                        number: i.number,
+                    },
+                    GhIssue {
+                        id: i.id,
+                        is_pull_request: false,
+                        parent_id: i.parent.map(|p| p.id),
+                    },
+                ),
+                ItemContent::PullRequest(pr) => (
+                    IssueKey {
+                        owner: pr.repository.owner.login,
+                        repo: pr.repository.name,
+                        number: pr.number,
+                    },
+                    GhIssue {
+                        id: pr.id,
+                        is_pull_request: true,
+                        parent_id: None,
+                    },
+                ),
+                ItemContent::DraftIssue => return None,
+            };
+            let field_values = item
+                .field_values
+                .nodes
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+                .filter_map(|value| match value {
+                    ItemFieldValue::ProjectV2ItemFieldNumberValue(v) => {
+                        Some((item_field_id(&v.field), FieldValue::Number(v.number)))
+                    }
+                    ItemFieldValue::ProjectV2ItemFieldSingleSelectValue(v) => Some((
+                        item_field_id(&v.field),
+                        FieldValue::SingleSelect(v.option_id),
+                    )),
+                    ItemFieldValue::ProjectV2ItemFieldIterationValue(v) => Some((
+                        item_field_id(&v.field),
+                        FieldValue::Iteration(Some(v.iteration_id)),
+                    )),
+                    ItemFieldValue::ProjectV2ItemFieldTextValue(v) => {
+                        Some((item_field_id(&v.field), FieldValue::Text(v.text)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            Some((
+                key,
+                ProjectItem {
+                    item_id: item.id,
+                    position,
+                    content,
+                    archived: item.is_archived,
+                    field_values,
+                },
+            ))
+        })
+        .collect()
+}
+
+pub fn item_field_id(field: &ItemFieldId) -> String {
+    match field {
+        ItemFieldId::ProjectV2Field(f) => f.id.clone(),
+        ItemFieldId::ProjectV2IterationField(f) => f.id.clone(),
+        ItemFieldId::ProjectV2SingleSelectField(f) => f.id.clone(),
+    }
+}
+
+/// Which of the desired field values differ from what the item already has. A missing item (one
+/// we're about to add) has no values.
+pub fn changed_fields(
+    existing: Option<&ProjectItem>,
+    desired: Vec<FieldChange>,
+) -> Vec<FieldChange> {
+    desired
+        .into_iter()
+        .filter_map(|mut change| {
+            match existing.and_then(|i| i.field_values.get(&change.field_id)) {
+                Some(current) if current == &change.value => None,
+                Some(current) => {
+                    change.previous = current.clone();
+                    Some(change)
+                }
+                None => (!change.value.is_empty()).then_some(change),
+            }
+        })
+        .collect()
+}
+
+pub fn set_field(
+    client: Client,
+    project_id: &str,
+    item_id: &str,
+    field_id: &str,
+    value: FieldValue,
+) -> Result<String, ApiError> {
+    // GH won't take an empty value through the update mutation
+    if value.is_empty() {
+        return github::clear_field(client, project_id, item_id, field_id);
+    }
+    match value {
+        FieldValue::Number(v) => github::set_field_number(client, project_id, item_id, field_id, v),
+        FieldValue::SingleSelect(v) => {
+            github::set_field_option(client, project_id, item_id, field_id, v)
+        }
+        FieldValue::Text(v) => github::set_field_value(client, project_id, item_id, field_id, v),
+        FieldValue::Iteration(v) => {
+            github::set_field_iteration(client, project_id, item_id, field_id, v)
+        }
+    }
+}
+
+/// How a plan is run, as opposed to what it syncs.
+pub struct PlanOptions<'a> {
+    /// Checkpoint of an earlier sync to pick up from.
+    pub resume: Option<&'a Checkpoint>,
+    /// Return the first per-issue error instead of recording it and carrying on.
+    pub fail_fast: bool,
+}
+
+/// Read everything from ZH and GH and work out which mutations are needed, without making any.
+pub fn plan(
+    github_client: Client,
+    zenhub_client: Client,
+    config: &Config,
+    workspace: zenhub::Workspace,
+    project_id: &str,
+    gh_fields: &[ProjectField],
+    options: PlanOptions,
+) -> Result<Plan, Error> {
+    let PlanOptions { resume, fail_fast } = options;
+    let field_mapping = &config.fields;
+    let lane_mapping = &config.lanes;
+    let priority_mapping = &config.priorities;
+
+    info!("Getting existing GitHub project items");
+    let project_items = index_project_items(github::get_project_items(
+        github_client.clone(),
+        project_id,
+    )?);
+    info!(
+        items = project_items.len(),
+        "Found existing GitHub project items"
+    );
+    let estimate_field_id = zh_to_gh_field_id("Estimate", field_mapping, gh_fields)?;
+    let priority_field_id = zh_to_gh_field_id("Priority", field_mapping, gh_fields)?;
+    let status_field_id = zh_to_gh_field_id("Pipeline", field_mapping, gh_fields)?;
+    // sprints are optional, only synced if they're mapped
+    let sprint_field_id = field_mapping
+        .contains_key("Sprint")
+        .then(|| zh_to_gh_field_id("Sprint", field_mapping, gh_fields))
+        .transpose()?;
+    let blocking_field_id = field_mapping
+        .contains_key("Blocking")
+        .then(|| zh_to_gh_field_id("Blocking", field_mapping, gh_fields))
+        .transpose()?;
+    // epics often aren't in the project themselves, so remember the ones we've had to look up
+    let mut epic_issues: HashMap<IssueKey, Option<GhIssue>> = HashMap::new();
+    let mut plan = Plan::default();
+    let closed_status_id = match (config.closed.policy, &config.closed.status) {
+        (ClosedPolicy::Skip, _) => None,
+        (_, Some(status)) => Some(gh_status_option_id(status, field_mapping, gh_fields)?),
+        (_, None) => unreachable!("config validation requires closed.status"),
+    };
+    plan.summary.closed_policy = config.closed.policy;
+    // the closed pipeline isn't usually in the workspace's pipelines, but closed issues are only
+    // worth reading when they're going to be synced
+    let mut pipelines: Vec<(String, String)> = workspace
+        .pipelines_connection
+        .nodes
+        .into_iter()
+        .map(|p| (p.id, p.name))
+        .collect();
+    let closed_pipeline_id = workspace.closed_pipeline.id;
+    if config.closed.policy != ClosedPolicy::Skip
+        && !pipelines.iter().any(|(id, _)| id == &closed_pipeline_id)
+    {
+        pipelines.push((closed_pipeline_id.clone(), workspace.closed_pipeline.name));
+    }
+    // every synced issue in ZH order
+    let mut order: Vec<OrderedItem> = vec![];
+    let synced = resume.map(|c| c.synced_issues()).unwrap_or_default();
+    let mut resume_from = resume.and_then(|c| c.resume_from.clone());
+    if let Some(checkpoint) = resume {
+        info!(
+            synced = checkpoint.synced.len(),
+            "Resuming from the checkpoint"
+        );
+        // the rest of the items get lined up after the ones that are already done
+        order.extend(checkpoint.synced.iter().filter_map(|s| {
+            project_items
+                .get(&s.issue)
+                .filter(|i| !i.archived)
+                .map(|i| OrderedItem {
+                    issue: s.issue.clone(),
+                    pipeline: s.pipeline.clone(),
+                    existing: Some((i.item_id.clone(), i.position)),
+                })
+        }));
+    }
+    // unchanged issues waiting to be checkpointed with the next planned one
+    let mut unchanged: Vec<UnchangedIssue> = vec![];
+
+    for (pipeline_id, pipeline_name) in pipelines {
+        let start_cursor = match &resume_from {
+            Some(point) if point.pipeline_id != pipeline_id => {
+                info!(pipeline = %pipeline_name, "Skipping Zenhub pipeline, it was already synced");
+                continue;
+            }
+            Some(_) => resume_from.take().and_then(|point| point.cursor),
+            None => None,
+        };
+        info!(pipeline = %pipeline_name, "Getting issues for Zenhub pipeline");
+        let issues = zenhub::get_pipeline_issues(
+            zenhub_client.clone(),
+            &pipeline_id,
+            &workspace.id,
+            start_cursor,
+        )?;
+        for PagedIssue {
+            page,
+            cursor,
+            issue: zh_issue,
+        } in issues
+        {
+            let issue_key = IssueKey {
+                owner: zh_issue.repository.owner.login.clone(),
+                repo: zh_issue.repository.name.clone(),
+                number: zh_issue.number,
+            };
+            if synced.contains(&issue_key) {
+                continue;
+            }
+            let span = issue_span(&issue_key, &pipeline_name, None);
+            let _entered = span.enter();
+            // the outcome is settled below, once it's known what the issue needs
+            plan.summary.report.push(
+                &issue_key,
+                &pipeline_name,
+                &zh_issue.html_url,
+                Outcome::Unchanged,
+            );
+            // issues can be closed without ZH having moved them to the closed pipeline yet
+            let closed =
+                pipeline_id == closed_pipeline_id || matches!(zh_issue.state, IssueState::CLOSED);
+            if closed {
+                plan.summary.closed += 1;
+                if config.closed.policy == ClosedPolicy::Skip {
+                    if let Some(row) = plan.summary.report.get_mut(&issue_key) {
+                        row.outcome = Outcome::Skipped;
+                    }
+                    continue;
+                }
+            }
+            let existing_item = project_items.get(&issue_key);
+            if let Some(item) = existing_item {
+                span.record("item_id", item.item_id.as_str());
+            }
+            let gh_issue = match existing_item {
+                Some(item) => item.content.clone(),
+                None => {
+                    debug!("Getting GitHub issue");
+                    match get_gh_issue(github_client.clone(), &issue_key) {
+                        // anything other than the issue not existing is a real problem
+                        Err(e) if !e.is_not_found() => {
+                            plan.summary.record_failure(
+                                fail_fast,
+                                &issue_key,
+                                "look up the GH issue",
+                                e.into(),
+                            )?;
+                            continue;
+                        }
+                        // never fatal, since ZH is full of issues that were deleted from GH
+                        Err(e) => {
+                            warn!(error = %e, "Missing GH issue. Usually when this happens the issue or creator of it have been deleted");
+                            plan.summary.missing += 1;
+                            if let Some(row) = plan.summary.report.get_mut(&issue_key) {
+                                row.outcome = Outcome::Missing;
+                                row.error = Some(e.to_string());
+                            }
+                            plan.summary.failures.push(Failure {
+                                issue: issue_key.clone(),
+                                step: "look up the GH issue".to_string(),
+                                error: e.to_string(),
+                            });
+                            continue;
+                        }
+                        Ok(gh_issue) => gh_issue,
+                    }
+                }
+            };
+            let add_content_id = existing_item.is_none().then(|| gh_issue.id.clone());
+            let estimate = zh_issue.estimate.map(|e| e.value);
+            // issues ZH doesn't consider part of the workspace board have no pipeline issue, so no priority
+            let zh_priority = zh_issue.pipeline_issue.and_then(|p| p.priority);
+            let gh_priority = priority_mapping.gh_option(
+                zh_priority
+                    .as_ref()
+                    .map(|p| (p.id.as_str(), p.name.as_str())),
+            );
+            if let (Some(p), None) = (&zh_priority, gh_priority) {
+                warn!(
+                    priority = %p.name,
+                    "ZH priority isn't mapped, leaving it unset"
+                );
+            }
+            let priority_label = gh_priority.cloned();
+            let priority_option_id = match zh_to_gh_priority(gh_priority, field_mapping, gh_fields)
+            {
+                Ok(id) => id,
+                Err(e) => {
+                    plan.summary
+                        .record_failure(fail_fast, &issue_key, "map the priority", e)?;
+                    continue;
+                }
+            };
+            // status field based on current pipeline, or the closed status
+            let (status_option_id, status_label) = match &closed_status_id {
+                Some(id) if closed => (id.clone(), config.closed.status.clone()),
+                _ => {
+                    match zh_to_gh_status_id(&pipeline_name, lane_mapping, field_mapping, gh_fields)
+                    {
+                        Ok(id) => (id, lane_mapping.get(&pipeline_name).cloned()),
+                        Err(e) => {
+                            plan.summary.record_failure(
+                                fail_fast,
+                                &issue_key,
+                                "map the status",
+                                e,
+                            )?;
+                            continue;
+                        }
+                    }
+                }
+            };
+            // no point ordering items that are about to be archived
+            if !(closed && config.closed.policy == ClosedPolicy::Archive) {
+                order.push(OrderedItem {
+                    issue: issue_key.clone(),
+                    pipeline: pipeline_name.clone(),
+                    existing: existing_item.map(|i| (i.item_id.clone(), i.position)),
+                });
+            }
+
+            let mut sub_issue = None;
+            if config.epics.sub_issues {
+                // ZH wants parentZenhubEpics now, but those aren't backed by GH issues. Issue based
+                // epics are the only ones that can become a GH parent issue.
+                #[allow(deprecated)]
+                let parent_epics = &zh_issue.parent_epics.nodes;
+                if let Some(link) = plan_sub_issue(
+                    github_client.clone(),
+                    &project_items,
+                    &mut epic_issues,
+                    &issue_key,
+                    &gh_issue,
+                    parent_epics,
+                ) {
+                    sub_issue = Some(link);
+                    plan.summary.sub_issues += 1;
+                }
+            }
+
+            let mut desired = vec![
+                FieldChange::new(
+                    estimate_field_id.clone(),
+                    field_mapping["Estimate"].clone(),
+                    FieldValue::Number(estimate),
+                    label(estimate.map(|e| e.to_string())),
+                ),
+                FieldChange::new(
+                    priority_field_id.clone(),
+                    field_mapping["Priority"].clone(),
+                    FieldValue::SingleSelect(priority_option_id),
+                    label(priority_label.clone()),
+                ),
+                FieldChange::new(
+                    status_field_id.clone(),
+                    field_mapping["Pipeline"].clone(),
+                    FieldValue::SingleSelect(Some(status_option_id)),
+                    label(status_label.clone()),
+                ),
+            ];
+            if let Some(sprint_field_id) = &sprint_field_id {
+                match zh_to_gh_iteration(
+                    &zh_issue.sprints.nodes,
+                    config.sprints.match_by,
+                    field_mapping,
+                    gh_fields,
+                ) {
+                    Ok(iteration) => desired.push(FieldChange::new(
+                        sprint_field_id.clone(),
+                        field_mapping["Sprint"].clone(),
+                        FieldValue::Iteration(iteration.as_ref().map(|(id, _)| id.clone())),
+                        label(iteration.map(|(_, title)| title)),
+                    )),
+                    // leave whatever iteration GH has alone rather than failing the whole run
+                    Err(e) => warn!(error = %e, "Not syncing the sprint"),
+                }
+            }
+            let mut comment = None;
+            if let Some(blocking_field_id) = &blocking_field_id {
+                let blocked_by = dependency_list("Blocked by", &zh_issue.blocking_issues.nodes);
+                let blocks = dependency_list("Blocks", &zh_issue.blocked_issues.nodes);
+                if let (true, Some(blocked_by)) = (config.blocking.comment, &blocked_by) {
+                    // only comment when the blocked by list is new or has changed since the last run
+                    let already_recorded = matches!(
+                        existing_item.and_then(|i| i.field_values.get(blocking_field_id)),
+                        Some(FieldValue::Text(Some(text))) if text.split("; ").any(|part| part == blocked_by)
+                    );
+                    if !already_recorded {
+                        comment = Some(PlannedComment {
+                            issue: issue_key.clone(),
+                            subject_id: gh_issue.id.clone(),
+                            body: format!("{blocked_by}. Carried over from Zenhub."),
+                            field_id: blocking_field_id.clone(),
+                        });
+                        plan.summary.comments += 1;
+                    }
+                }
+                let text = [blocked_by, blocks]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                let text = (!text.is_empty()).then_some(text);
+                desired.push(FieldChange::new(
+                    blocking_field_id.clone(),
+                    field_mapping["Blocking"].clone(),
+                    FieldValue::Text(text.clone()),
+                    label(text),
+                ));
+            }
+            let changes = changed_fields(existing_item, desired);
+            let outcome = match existing_item {
+                None => {
+                    plan.summary.added += 1;
+                    Outcome::Added
+                }
+                Some(_) if changes.is_empty() => {
+                    plan.summary.unchanged += 1;
+                    Outcome::Unchanged
+                }
+                Some(_) => {
+                    plan.summary.updated += 1;
+                    Outcome::Updated
+                }
+            };
+            if let Some(row) = plan.summary.report.get_mut(&issue_key) {
+                row.status = status_label;
+                row.estimate = estimate;
+                row.priority = priority_label;
+                row.item_id = existing_item.map(|i| i.item_id.clone());
+                row.outcome = outcome;
+            }
+            // items that were archived on an earlier run are left that way
+            let archive = closed
+                && config.closed.policy == ClosedPolicy::Archive
+                && !existing_item.is_some_and(|i| i.archived);
+            if add_content_id.is_none()
+                && changes.is_empty()
+                && sub_issue.is_none()
+                && comment.is_none()
+                && !archive
+            {
+                if let Some(item) = existing_item {
+                    unchanged.push(UnchangedIssue {
+                        synced: SyncedIssue {
+                            issue: issue_key,
+                            pipeline: pipeline_name.clone(),
+                            item_id: item.item_id.clone(),
+                        },
+                        pipeline_id: pipeline_id.clone(),
+                        page,
+                        cursor,
+                    });
+                }
+                continue;
+            }
+            plan.items.push(ItemPlan {
+                issue: issue_key,
+                pipeline: pipeline_name.clone(),
+                item_id: existing_item.map(|i| i.item_id.clone()),
+                add_content_id,
+                changes,
+                sub_issue,
+                comment,
+                archive,
+                pipeline_id: pipeline_id.clone(),
+                page,
+                cursor,
+                unchanged_before: mem::take(&mut unchanged),
+            });
+        }
+    }
+    if let Some(point) = resume_from {
+        warn!(
+            pipeline_id = %point.pipeline_id,
+            "The checkpoint's pipeline isn't in the workspace anymore, nothing was resumed"
+        );
+    }
+    plan.moves = plan_moves(&order);
+    plan.summary.moved = plan.moves.len();
+
+    Ok(plan)
+}
+
+// An issue's place in the ZH order, with the GH item ID and position if it's already in the project.
+#[derive(Debug, Clone)]
+struct OrderedItem {
+    issue: IssueKey,
+    pipeline: String,
+    existing: Option<(String, usize)>,
+}
+
+// Keep the longest run of items that are already in the right relative order where they are, and
+// move everything else straight after the item that should come before it. New items always get
+// moved since there's no telling where GH puts them.
+fn plan_moves(order: &[OrderedItem]) -> Vec<ItemMove> {
+    // patience sort over the existing positions, `tails[n]` is the index in `order` ending the best
+    // increasing run of length n + 1
+    let mut tails: Vec<usize> = vec![];
+    let mut previous: Vec<Option<usize>> = vec![None; order.len()];
+    let position_of = |j: usize| {
+        order[j]
+            .existing
+            .as_ref()
+            .map(|(_, p)| *p)
+            .unwrap_or_default()
+    };
+    for (i, item) in order.iter().enumerate() {
+        let Some((_, position)) = &item.existing else {
+            continue;
+        };
+        let n = tails.partition_point(|&j| position_of(j) < *position);
+        previous[i] = n.checked_sub(1).map(|n| tails[n]);
+        if n == tails.len() {
+            tails.push(i);
+        } else {
+            tails[n] = i;
+        }
+    }
+    let mut in_place = vec![false; order.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        in_place[i] = true;
+        next = previous[i];
+    }
+
+    order
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !in_place[*i])
+        .map(|(i, item)| {
+            let after = i.checked_sub(1).map(|i| &order[i]);
+            ItemMove {
+                issue: item.issue.clone(),
+                pipeline: item.pipeline.clone(),
+                item_id: item.existing.as_ref().map(|(id, _)| id.clone()),
+                after: after.map(|a| a.issue.clone()),
+                after_item_id: after.and_then(|a| a.existing.as_ref().map(|(id, _)| id.clone())),
+            }
+        })
+        .collect()
+}
+
+fn get_gh_issue(client: Client, key: &IssueKey) -> Result<GhIssue, ApiError> {
+    use github::get_issue_or_pr::GetIssueOrPrRepositoryIssueOrPullRequest as IssueOrPr;
+
+    Ok(
+        match github::get_issue_or_pr(client, &key.owner, &key.repo, key.number)? {
+            IssueOrPr::Issue(issue) => GhIssue {
+                id: issue.id,
+                is_pull_request: false,
+                parent_id: issue.parent.map(|p| p.id),
+            },
+            IssueOrPr::PullRequest(pr) => GhIssue {
+                id: pr.id,
+                is_pull_request: true,
+                parent_id: None,
+            },
+        },
+    )
+}
+
+// Work out whether a child issue needs to be attached to its ZH epic in GH. Children can live in a
+// different repo than their epic, which is fine since everything is looked up by node ID.
+fn plan_sub_issue(
+    github_client: Client,
+    project_items: &HashMap<IssueKey, ProjectItem>,
+    epic_issues: &mut HashMap<IssueKey, Option<GhIssue>>,
+    child: &IssueKey,
+    child_issue: &GhIssue,
+    parent_epics: &[ParentEpic],
+) -> Option<SubIssueLink> {
+    // GH issues can only have one parent, so the first ZH epic wins
+    let epic = parent_epics.first()?;
+    let parent = IssueKey {
+        owner: epic.issue.repository.owner.login.clone(),
+        repo: epic.issue.repository.name.clone(),
+        number: epic.issue.number,
+    };
+    if parent_epics.len() > 1 {
+        warn!(
+            epics = parent_epics.len(),
+            %parent,
+            "Issue is in more than one ZH epic, only linking it to the first"
+        );
+    }
+    if child_issue.is_pull_request {
+        warn!(%parent, "PRs can't be sub-issues, not linking it to its ZH epic");
+        return None;
+    }
+    let parent_issue = epic_issues
+        .entry(parent.clone())
+        .or_insert_with(|| match project_items.get(&parent) {
+            Some(item) => Some(item.content.clone()),
+            None => get_gh_issue(github_client, &parent)
+                .inspect_err(|e| warn!(%parent, error = %e, "Missing GH issue for ZH epic"))
+                .ok(),
+        })
+        .clone()?;
+    match &child_issue.parent_id {
+        Some(parent_id) if parent_id == &parent_issue.id => None,
+        Some(_) => {
+            warn!(%parent, "Issue already has a different parent in GH, not making it a sub-issue of its ZH epic");
+            None
+        }
+        None => Some(SubIssueLink {
+            parent,
+            child: child.clone(),
+            parent_id: parent_issue.id,
+            child_id: child_issue.id.clone(),
+        }),
+    }
+}
+
+// i.e. "Blocked by owner/repo#1, owner/other-repo#2", `None` when there aren't any issues
+fn dependency_list(prefix: &str, issues: &[IssueRef]) -> Option<String> {
+    (!issues.is_empty()).then(|| {
+        let keys: Vec<String> = issues
+            .iter()
+            .map(|i| {
+                IssueKey {
+                    owner: i.repository.owner.login.clone(),
+                    repo: i.repository.name.clone(),
+                    number: i.number,
+                }
+                .to_string()
+            })
+            .collect();
+        format!("{prefix} {}", keys.join(", "))
+    })
+}
+
+// how the closed policy's outcome reads in the summaries, i.e. "3 closed issues archived"
+pub fn closed_outcome(policy: ClosedPolicy) -> &'static str {
+    match policy {
+        ClosedPolicy::Skip => "skipped",
+        ClosedPolicy::Done => "synced as done",
+        ClosedPolicy::Archive => "archived",
+    }
+}
+
+// Everything logged while planning or applying one issue is in its span, so JSON logs can be
+// filtered down to a single issue. `item_id` is recorded once it's known.
+fn issue_span(issue: &IssueKey, pipeline: &str, item_id: Option<&str>) -> Span {
+    info_span!(
+        "issue",
+        repo = %format_args!("{}/{}", issue.owner, issue.repo),
+        number = issue.number,
+        pipeline,
+        item_id = item_id.map(field::display),
+    )
+}
+
+fn label(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "(empty)".to_string())
+}
+
+/// Make every mutation in the plan. Each issue is recorded in the checkpoint once all of its own
+/// mutations are done, moves happen at the end since they depend on every item being in the project.
+/// Issues that fail are added to the summary's failures unless `fail_fast` is set.
+pub fn apply(
+    github_client: Client,
+    project_id: &str,
+    plan: Plan,
+    checkpoint: &mut Checkpoint,
+    journal: &mut Journal,
+    fail_fast: bool,
+) -> Result<Summary, Error> {
+    let mut summary = plan.summary;
+    // the plan's counts are what it meant to do, they're counted again from what actually got done
+    summary.added = 0;
+    summary.updated = 0;
+    summary.sub_issues = 0;
+    summary.comments = 0;
+    summary.moved = 0;
+    let resume_points = resume_points(&plan.items);
+    // once an issue fails the resume point stops moving, so `--resume` reads it again. Issues that
+    // failed while planning could be anywhere, so those send it back to the start.
+    let mut held_resume: Option<Option<ResumePoint>> =
+        (!summary.failures.is_empty()).then_some(None);
+    // item IDs for the issues added in this run, so they can be moved into place afterwards
+    let mut added_items: HashMap<IssueKey, String> = HashMap::new();
+    for (mut item, resume_from) in plan.items.into_iter().zip(resume_points) {
+        let issue = item.issue.clone();
+        let pipeline = item.pipeline.clone();
+        let mut synced: Vec<SyncedIssue> = mem::take(&mut item.unchanged_before)
+            .into_iter()
+            .map(|u| u.synced)
+            .collect();
+        let adding = item.item_id.is_none();
+        let updating = !item.changes.is_empty();
+        let linking = item.sub_issue.is_some();
+        let commenting = item.comment.is_some();
+        let span = issue_span(&issue, &pipeline, item.item_id.as_deref());
+        let _entered = span.enter();
+        match apply_item(
+            github_client.clone(),
+            project_id,
+            item,
+            journal,
+            &mut added_items,
+        ) {
+            Ok(item_id) => {
+                if adding {
+                    summary.added += 1;
+                } else if updating {
+                    summary.updated += 1;
+                }
+                summary.sub_issues += usize::from(linking);
+                summary.comments += usize::from(commenting);
+                if let Some(row) = summary.report.get_mut(&issue) {
+                    row.item_id = Some(item_id.clone());
+                }
+                synced.push(SyncedIssue {
+                    issue,
+                    pipeline,
+                    item_id,
+                });
+                checkpoint.record(synced, held_resume.clone().unwrap_or(resume_from))?
+            }
+            Err(e) => {
+                // the plan counted it as unchanged if it had no fields to set, now it's only a failure
+                if !adding && !updating {
+                    summary.unchanged = summary.unchanged.saturating_sub(1);
+                }
+                let resume_from = held_resume
+                    .get_or_insert_with(|| checkpoint.resume_from.clone())
+                    .clone();
+                checkpoint.record(synced, resume_from)?;
+                match e {
+                    ItemError::Fatal(e) => return Err(e),
+                    ItemError::Api { step, error } => {
+                        summary.record_failure(fail_fast, &issue, &step, error.into())?
+                    }
+                }
+            }
+        }
+    }
+    // moves go in ZH order, so each item's predecessor is already where it belongs
+    let mut not_added: HashMap<IssueKey, Option<String>> = HashMap::new();
+    for m in plan.moves {
+        // an item that failed to be added was already reported, the item after it goes after the
+        // nearest one before it that's in the project instead, or to the top if there isn't one
+        let after_item_id = m.after.as_ref().and_then(|after| {
+            m.after_item_id
+                .clone()
+                .or_else(|| added_items.get(after).cloned())
+                .or_else(|| not_added.get(after).cloned().flatten())
+        });
+        let Some(item_id) = m.item_id.or_else(|| added_items.get(&m.issue).cloned()) else {
+            not_added.insert(m.issue, after_item_id);
+            continue;
+        };
+        let span = issue_span(&m.issue, &m.pipeline, Some(&item_id));
+        let _entered = span.enter();
+        info!("Moving the item into its ZH position");
+        if let Err(e) = github::update_item_position(
+            github_client.clone(),
+            project_id,
+            &item_id,
+            after_item_id.as_deref(),
+        ) {
+            summary.record_failure(fail_fast, &m.issue, "move the item", e.into())?;
+            continue;
+        }
+        journal.record(Entry::MoveItem {
+            issue: m.issue,
+            item_id,
+            after_item_id,
+        })?;
+        summary.moved += 1;
+    }
+    // written even when nothing was applied, so there's always a checkpoint to resume the failures from
+    if !summary.failures.is_empty() {
+        checkpoint.record(vec![], held_resume.flatten())?;
+    }
+
+    Ok(summary)
+}
+
+// API failures only fail the one issue, anything else (i.e. the journal can't be written) ends the run
+enum ItemError {
+    Api { step: String, error: ApiError },
+    Fatal(Error),
+}
+
+impl From<Error> for ItemError {
+    fn from(e: Error) -> Self {
+        ItemError::Fatal(e)
+    }
+}
+
+fn failed(step: impl Into<String>) -> impl FnOnce(ApiError) -> ItemError {
+    let step = step.into();
+    move |error| ItemError::Api { step, error }
+}
+
+// every mutation for one issue, returning its project item ID
+fn apply_item(
+    github_client: Client,
+    project_id: &str,
+    item: ItemPlan,
+    journal: &mut Journal,
+    added_items: &mut HashMap<IssueKey, String>,
+) -> Result<String, ItemError> {
+    let item_id = match (item.item_id, item.add_content_id) {
+        (Some(item_id), _) => item_id,
+        (None, Some(content_id)) => {
+            info!(%content_id, "Adding the issue to the project");
+            let item_id = github::add_item(github_client.clone(), project_id, &content_id)
+                .map_err(failed("add the item to the project"))?;
+            Span::current().record("item_id", item_id.as_str());
+            info!("Added the issue to the project");
+            journal.record(Entry::AddItem {
+                issue: item.issue.clone(),
+                item_id: item_id.clone(),
+                content_id,
+            })?;
+            added_items.insert(item.issue.clone(), item_id.clone());
+            item_id
+        }
+        (None, None) => unreachable!("planned items are either in the project or being added"),
+    };
+    // whether to comment is decided by what the Blocking field already says, so it's only set once
+    // the comment is posted. Otherwise a comment that failed would never be retried.
+    let (after_comment, changes): (Vec<_>, Vec<_>) = item.changes.into_iter().partition(|change| {
+        item.comment
+            .as_ref()
+            .is_some_and(|c| c.field_id == change.field_id)
+    });
+    for change in changes {
+        apply_change(
+            github_client.clone(),
+            project_id,
+            &item.issue,
+            &item_id,
+            change,
+            journal,
+        )?;
+    }
+    if let Some(link) = item.sub_issue {
+        info!(parent = %link.parent, "Making the issue a sub-issue of its ZH epic");
+        github::add_sub_issue(github_client.clone(), &link.parent_id, &link.child_id)
+            .map_err(failed("add the sub-issue"))?;
+        journal.record(Entry::AddSubIssue {
+            parent: link.parent,
+            child: link.child,
+            parent_id: link.parent_id,
+            child_id: link.child_id,
+        })?;
+    }
+    if let Some(comment) = item.comment {
+        info!("Commenting on the issue");
+        let comment_id =
+            github::add_comment(github_client.clone(), &comment.subject_id, &comment.body)
+                .map_err(failed("add the comment"))?;
+        journal.record(Entry::AddComment {
+            issue: comment.issue,
+            comment_id,
+        })?;
+    }
+    for change in after_comment {
+        apply_change(
+            github_client.clone(),
+            project_id,
+            &item.issue,
+            &item_id,
+            change,
+            journal,
+        )?;
+    }
+    if item.archive {
+        info!("Archiving the closed issue");
+        github::archive_item(github_client.clone(), project_id, &item_id)
+            .map_err(failed("archive the item"))?;
+        journal.record(Entry::ArchiveItem {
+            issue: item.issue.clone(),
+            item_id: item_id.clone(),
+        })?;
+    }
+    Ok(item_id)
+}
+
+fn apply_change(
+    github_client: Client,
+    project_id: &str,
+    issue: &IssueKey,
+    item_id: &str,
+    change: FieldChange,
+    journal: &mut Journal,
+) -> Result<(), ItemError> {
+    info!(
+        field = %change.field_name,
+        value = %change.label,
+        "Setting the field"
+    );
+    set_field(
+        github_client,
+        project_id,
+        item_id,
+        &change.field_id,
+        change.value.clone(),
+    )
+    .map_err(failed(format!("set {}", change.field_name)))?;
+    journal.record(Entry::SetField {
+        issue: issue.clone(),
+        item_id: item_id.to_string(),
+        field_id: change.field_id,
+        field_name: change.field_name,
+        previous: change.previous,
+        value: change.value,
+    })?;
+    Ok(())
+}
+
+// Where a resumed run should start reading once each item is done: the earliest page in the next
+// pipeline with an issue that isn't checkpointed yet. That's the planned items still to come and the
+// unchanged issues waiting on them, which can be in an earlier pipeline than the item they're waiting
+// on. Issues are sorted by position after they're read, so later issues can come from earlier pages.
+fn resume_points(items: &[ItemPlan]) -> Vec<Option<ResumePoint>> {
+    let mut points = vec![None; items.len()];
+    // (pipeline ID, page, cursor)
+    let mut earliest: Option<(&str, usize, &Option<String>)> = None;
+    for (i, item) in items.iter().enumerate().rev() {
+        points[i] = earliest.map(|(pipeline_id, _, cursor)| ResumePoint {
+            pipeline_id: pipeline_id.to_string(),
+            cursor: cursor.clone(),
+        });
+        // walking backwards through ZH order, so a different pipeline is always an earlier one
+        let left = iter::once((item.pipeline_id.as_str(), item.page, &item.cursor)).chain(
+            item.unchanged_before
+                .iter()
+                .rev()
+                .map(|u| (u.pipeline_id.as_str(), u.page, &u.cursor)),
+        );
+        for read_from in left {
+            earliest = match earliest {
+                Some(e) if e.0 == read_from.0 && e.1 <= read_from.1 => Some(e),
+                _ => Some(read_from),
+            };
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(number: i64) -> IssueKey {
+        IssueKey {
+            owner: "acme".to_string(),
+            repo: "app".to_string(),
+            number,
+        }
+    }
+
+    // issue `number`, at `position` in the project if it's already there
+    fn ordered(number: i64, position: Option<usize>) -> OrderedItem {
+        OrderedItem {
+            issue: key(number),
+            pipeline: "Backlog".to_string(),
+            existing: position.map(|p| (format!("item-{number}"), p)),
+        }
+    }
+
+    // (issue, after, after_item_id) for each move
+    fn moves(order: &[OrderedItem]) -> Vec<(i64, Option<i64>, Option<String>)> {
+        plan_moves(order)
+            .into_iter()
+            .map(|m| (m.issue.number, m.after.map(|a| a.number), m.after_item_id))
+            .collect()
+    }
+
+    #[test]
+    fn plan_moves_leaves_ordered_items_alone() {
+        assert!(moves(&[]).is_empty());
+        assert!(moves(&[
+            ordered(1, Some(0)),
+            ordered(2, Some(1)),
+            ordered(3, Some(5))
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn plan_moves_only_moves_items_out_of_order() {
+        assert_eq!(
+            moves(&[
+                ordered(1, Some(0)),
+                ordered(2, Some(3)),
+                ordered(3, Some(1)),
+                ordered(4, Some(2)),
+            ]),
+            vec![(2, Some(1), Some("item-1".to_string()))]
+        );
+    }
+
+    #[test]
+    fn plan_moves_moves_to_the_top() {
+        assert_eq!(
+            moves(&[
+                ordered(1, Some(2)),
+                ordered(2, Some(0)),
+                ordered(3, Some(1))
+            ]),
+            vec![(1, None, None)]
+        );
+        assert_eq!(
+            moves(&[
+                ordered(1, Some(2)),
+                ordered(2, Some(1)),
+                ordered(3, Some(0))
+            ]),
+            vec![(1, None, None), (2, Some(1), Some("item-1".to_string()))]
+        );
+    }
+
+    #[test]
+    fn plan_moves_always_moves_new_items() {
+        assert_eq!(
+            moves(&[
+                ordered(1, None),
+                ordered(2, Some(0)),
+                ordered(3, None),
+                ordered(4, None),
+            ]),
+            vec![
+                (1, None, None),
+                (3, Some(2), Some("item-2".to_string())),
+                // the item before it is new too, so its ID is only known once it's added
+                (4, Some(3), None),
+            ]
+        );
+    }
+
+    fn change(field_id: &str, value: FieldValue) -> FieldChange {
+        FieldChange::new(
+            field_id.to_string(),
+            field_id.to_string(),
+            value,
+            String::new(),
+        )
+    }
+
+    fn project_item(field_values: Vec<(&str, FieldValue)>) -> ProjectItem {
+        ProjectItem {
+            item_id: "item-1".to_string(),
+            position: 0,
+            content: GhIssue {
+                id: "issue-1".to_string(),
+                is_pull_request: false,
+                parent_id: None,
+            },
+            archived: false,
+            field_values: field_values
+                .into_iter()
+                .map(|(id, value)| (id.to_string(), value))
+                .collect(),
+        }
+    }
+
+    // (field ID, value, previous) for each change
+    fn changes(
+        existing: Option<&ProjectItem>,
+        desired: Vec<FieldChange>,
+    ) -> Vec<(String, FieldValue, FieldValue)> {
+        changed_fields(existing, desired)
+            .into_iter()
+            .map(|c| (c.field_id, c.value, c.previous))
+            .collect()
+    }
+
+    #[test]
+    fn changed_fields_skips_empty_values_for_new_items() {
+        assert_eq!(
+            changes(
+                None,
+                vec![
+                    change("estimate", FieldValue::Number(Some(3.0))),
+                    change("priority", FieldValue::SingleSelect(None)),
+                ]
+            ),
+            vec![(
+                "estimate".to_string(),
+                FieldValue::Number(Some(3.0)),
+                FieldValue::Number(None)
+            )]
+        );
+    }
+
+    #[test]
+    fn changed_fields_only_keeps_values_that_differ() {
+        let item = project_item(vec![
+            ("estimate", FieldValue::Number(Some(3.0))),
+            ("status", FieldValue::SingleSelect(Some("todo".to_string()))),
+            (
+                "blocking",
+                FieldValue::Text(Some("Blocks acme/app#2".to_string())),
+            ),
+        ]);
+        assert_eq!(
+            changes(
+                Some(&item),
+                vec![
+                    change("estimate", FieldValue::Number(Some(3.0))),
+                    change("status", FieldValue::SingleSelect(Some("done".to_string()))),
+                    change("blocking", FieldValue::Text(None)),
+                    change("priority", FieldValue::SingleSelect(None)),
+                    change(
+                        "sprint",
+                        FieldValue::Iteration(Some("sprint-1".to_string()))
+                    ),
+                ]
+            ),
+            vec![
+                (
+                    "status".to_string(),
+                    FieldValue::SingleSelect(Some("done".to_string())),
+                    FieldValue::SingleSelect(Some("todo".to_string()))
+                ),
+                // clearing a field remembers what it had so rollback can put it back
+                (
+                    "blocking".to_string(),
+                    FieldValue::Text(None),
+                    FieldValue::Text(Some("Blocks acme/app#2".to_string()))
+                ),
+                (
+                    "sprint".to_string(),
+                    FieldValue::Iteration(Some("sprint-1".to_string())),
+                    FieldValue::Iteration(None)
+                ),
+            ]
+        );
+    }
+
+    fn item_plan(number: i64, pipeline_id: &str, page: usize) -> ItemPlan {
+        ItemPlan {
+            issue: key(number),
+            pipeline: pipeline_id.to_string(),
+            item_id: Some(format!("item-{number}")),
+            add_content_id: None,
+            changes: vec![],
+            sub_issue: None,
+            comment: None,
+            archive: false,
+            pipeline_id: pipeline_id.to_string(),
+            page,
+            cursor: (page > 0).then(|| format!("{pipeline_id}-{page}")),
+            unchanged_before: vec![],
+        }
+    }
+
+    fn unchanged(number: i64, pipeline_id: &str, page: usize) -> UnchangedIssue {
+        let item = item_plan(number, pipeline_id, page);
+        UnchangedIssue {
+            synced: SyncedIssue {
+                issue: item.issue,
+                pipeline: item.pipeline,
+                item_id: item.item_id.unwrap(),
+            },
+            pipeline_id: item.pipeline_id,
+            page: item.page,
+            cursor: item.cursor,
+        }
+    }
+
+    // (pipeline ID, cursor) to resume from after each item
+    fn resumes(items: &[ItemPlan]) -> Vec<Option<(String, Option<String>)>> {
+        resume_points(items)
+            .into_iter()
+            .map(|p| p.map(|p| (p.pipeline_id, p.cursor)))
+            .collect()
+    }
+
+    fn point(pipeline_id: &str, cursor: Option<&str>) -> Option<(String, Option<String>)> {
+        Some((pipeline_id.to_string(), cursor.map(|c| c.to_string())))
+    }
+
+    #[test]
+    fn resume_points_follow_the_next_item() {
+        assert!(resumes(&[]).is_empty());
+        assert_eq!(
+            resumes(&[
+                item_plan(1, "todo", 0),
+                item_plan(2, "todo", 1),
+                item_plan(3, "doing", 0),
+                item_plan(4, "doing", 2),
+            ]),
+            vec![
+                point("todo", Some("todo-1")),
+                point("doing", None),
+                point("doing", Some("doing-2")),
+                // nothing left to resume once the last item is done
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn resume_points_go_back_to_the_earliest_page_left() {
+        // issues are sorted by position after they're read, so a later item can be on an earlier page
+        assert_eq!(
+            resumes(&[
+                item_plan(1, "todo", 2),
+                item_plan(2, "todo", 0),
+                item_plan(3, "todo", 1),
+                item_plan(4, "doing", 0),
+            ]),
+            vec![
+                point("todo", None),
+                point("todo", Some("todo-1")),
+                point("doing", None),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn resume_points_wait_for_unchanged_issues() {
+        // todo ends with an unchanged issue that's only checkpointed along with the first doing item
+        let mut first_doing = item_plan(3, "doing", 0);
+        first_doing.unchanged_before = vec![unchanged(2, "todo", 1)];
+        // and an unchanged issue on an earlier page than the item it's waiting on
+        let mut last_doing = item_plan(5, "doing", 2);
+        last_doing.unchanged_before = vec![unchanged(4, "doing", 1)];
+        assert_eq!(
+            resumes(&[item_plan(1, "todo", 0), first_doing, last_doing]),
+            vec![
+                point("todo", Some("todo-1")),
+                point("doing", Some("doing-1")),
+                None,
+            ]
+        );
+    }
+}
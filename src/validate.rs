@@ -0,0 +1,149 @@
+use crate::{
+    config::Config,
+    github::get_fields::{GetFieldsNodeOnProjectV2FieldsNodes as ProjectField, ProjectV2FieldType},
+    mapping::zh_to_gh_field_id,
+    zenhub::Workspace,
+};
+
+/// Check the config against the live ZH workspace and GH project fields. Returns every problem found
+/// rather than stopping at the first one.
+pub fn validate(config: &Config, workspace: &Workspace, gh_fields: &[ProjectField]) -> Vec<String> {
+    let mut problems = vec![];
+
+    let pipelines: Vec<&str> = workspace
+        .pipelines_connection
+        .nodes
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+    // closed issues go by [closed] rather than [lanes]
+    let closed_pipeline = workspace.closed_pipeline.name.as_str();
+    for pipeline in &pipelines {
+        if *pipeline != closed_pipeline && !config.lanes.contains_key(*pipeline) {
+            problems.push(format!("ZH pipeline {pipeline:?} has no entry in [lanes]."));
+        }
+    }
+    for zh_lane in config.lanes.keys() {
+        if !pipelines.contains(&zh_lane.as_str()) && zh_lane != closed_pipeline {
+            problems.push(format!(
+                "[lanes] maps {zh_lane:?}, but the ZH workspace has no pipeline with that name."
+            ));
+        }
+    }
+
+    for zh_field in config.fields.keys() {
+        if let Err(e) = zh_to_gh_field_id(zh_field, &config.fields, gh_fields) {
+            problems.push(e.to_string());
+        }
+    }
+    if let Some(gh_name) = config.fields.get("Estimate") {
+        match find_field(gh_fields, gh_name) {
+            Some(ProjectField::ProjectV2Field(f))
+                if matches!(f.data_type, ProjectV2FieldType::NUMBER) => {}
+            Some(_) => problems.push(format!(
+                "GH field {gh_name} (mapped from Estimate) must be a number field."
+            )),
+            None => {}
+        }
+    }
+    if let Some(gh_name) = config.fields.get("Sprint") {
+        match find_field(gh_fields, gh_name) {
+            Some(ProjectField::ProjectV2IterationField(_)) | None => {}
+            Some(_) => problems.push(format!(
+                "GH field {gh_name} (mapped from Sprint) must be an iteration field."
+            )),
+        }
+    }
+    if let Some(gh_name) = config.fields.get("Blocking") {
+        match find_field(gh_fields, gh_name) {
+            Some(ProjectField::ProjectV2Field(f))
+                if matches!(f.data_type, ProjectV2FieldType::TEXT) => {}
+            None => {}
+            Some(_) => problems.push(format!(
+                "GH field {gh_name} (mapped from Blocking) must be a text field."
+            )),
+        }
+    }
+    check_options(
+        &mut problems,
+        gh_fields,
+        config.fields.get("Pipeline"),
+        "Pipeline",
+        "[lanes]",
+        config.lanes.values(),
+    );
+    check_options(
+        &mut problems,
+        gh_fields,
+        config.fields.get("Pipeline"),
+        "Pipeline",
+        "[closed]",
+        config.closed.status.iter(),
+    );
+    check_options(
+        &mut problems,
+        gh_fields,
+        config.fields.get("Priority"),
+        "Priority",
+        "[priorities]",
+        config.priorities.gh_options(),
+    );
+    for zh_priority in config.priorities.map.keys() {
+        if !workspace
+            .priorities_connection
+            .nodes
+            .iter()
+            .any(|p| &p.id == zh_priority || &p.name == zh_priority)
+        {
+            problems.push(format!(
+                "[priorities.map] maps {zh_priority:?}, but the ZH workspace has no priority with that name or ID."
+            ));
+        }
+    }
+
+    problems
+}
+
+/// The GH project field with this name, whatever its type.
+pub(crate) fn find_field<'a>(
+    gh_fields: &'a [ProjectField],
+    gh_name: &str,
+) -> Option<&'a ProjectField> {
+    gh_fields.iter().find(|f| match f {
+        ProjectField::ProjectV2Field(f) => f.name == gh_name,
+        ProjectField::ProjectV2SingleSelectField(ssf) => ssf.name == gh_name,
+        ProjectField::ProjectV2IterationField(itf) => itf.name == gh_name,
+    })
+}
+
+// every GH option name the config maps to has to exist on the single select field
+fn check_options<'a>(
+    problems: &mut Vec<String>,
+    gh_fields: &[ProjectField],
+    gh_name: Option<&String>,
+    zh_field: &str,
+    section: &str,
+    option_names: impl Iterator<Item = &'a String>,
+) {
+    let Some(gh_name) = gh_name else {
+        return;
+    };
+    match find_field(gh_fields, gh_name) {
+        Some(ProjectField::ProjectV2SingleSelectField(ssf)) => {
+            let mut missing: Vec<&String> = option_names
+                .filter(|name| !ssf.options.iter().any(|o| &&o.name == name))
+                .collect();
+            missing.sort();
+            missing.dedup();
+            for name in missing {
+                problems.push(format!(
+                    "{section} maps to {name:?}, but GH field {gh_name} has no option with that name."
+                ));
+            }
+        }
+        Some(_) => problems.push(format!(
+            "GH field {gh_name} (mapped from {zh_field}) must be a single select field."
+        )),
+        None => {}
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+use tracing::info;
+
+use crate::{
+    config::{ClosedPolicy, Config},
+    github::{self, get_fields::GetFieldsNodeOnProjectV2FieldsNodes as ProjectField},
+    mapping::zh_to_gh_field_id,
+    sync::{index_project_items, FieldValue, IssueKey, ProjectItem},
+    zenhub::{self, get_pipeline_issues::IssueState, PagedIssue, Workspace},
+};
+
+/// A difference between the ZH workspace and the GH project.
+#[derive(Debug, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum Problem {
+    /// A synced field doesn't have the value ZH says it should.
+    Mismatch {
+        issue: IssueKey,
+        item_id: String,
+        field: String,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    /// A ZH issue that should be in the project isn't.
+    MissingFromProject { issue: IssueKey, pipeline: String },
+    /// A project item for an issue that isn't in any ZH pipeline.
+    NotInZenhub { issue: IssueKey, item_id: String },
+    /// A closed issue that should have been archived under the `archive` closed policy.
+    NotArchived { issue: IssueKey, item_id: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "(empty)".to_string());
+        match self {
+            Problem::Mismatch {
+                issue,
+                field,
+                expected,
+                actual,
+                ..
+            } => write!(
+                f,
+                "{issue} {field} is {} in GH, but should be {}.",
+                value(actual),
+                value(expected)
+            ),
+            Problem::MissingFromProject { issue, pipeline } => {
+                write!(
+                    f,
+                    "{issue} (ZH pipeline {pipeline}) isn't in the GH project."
+                )
+            }
+            Problem::NotInZenhub { issue, item_id } => {
+                write!(f, "{issue} (GH item {item_id}) isn't in any ZH pipeline.")
+            }
+            Problem::NotArchived { issue, item_id } => {
+                write!(
+                    f,
+                    "{issue} (GH item {item_id}) is closed but wasn't archived."
+                )
+            }
+        }
+    }
+}
+
+/// Re-read every ZH pipeline and the whole GH project and check the two agree on which issues are
+/// in the project and on their status, estimate, and priority. Returns every problem found.
+pub fn verify(
+    github_client: Client,
+    zenhub_client: Client,
+    config: &Config,
+    workspace: Workspace,
+    project_id: &str,
+    gh_fields: &[ProjectField],
+) -> Result<Vec<Problem>, Error> {
+    let field_mapping = &config.fields;
+    info!("Getting existing GitHub project items");
+    let project_items = index_project_items(github::get_project_items(github_client, project_id)?);
+    let estimate_field_id = zh_to_gh_field_id("Estimate", field_mapping, gh_fields)?;
+    let priority_field_id = zh_to_gh_field_id("Priority", field_mapping, gh_fields)?;
+    let status_field_id = zh_to_gh_field_id("Pipeline", field_mapping, gh_fields)?;
+    let option_names = option_names(gh_fields);
+
+    // closed issues are always read, even when they aren't synced, so their items don't look like
+    // they're missing from ZH
+    let mut pipelines: Vec<(String, String)> = workspace
+        .pipelines_connection
+        .nodes
+        .into_iter()
+        .map(|p| (p.id, p.name))
+        .collect();
+    let closed_pipeline_id = workspace.closed_pipeline.id;
+    if !pipelines.iter().any(|(id, _)| id == &closed_pipeline_id) {
+        pipelines.push((closed_pipeline_id.clone(), workspace.closed_pipeline.name));
+    }
+
+    let mut problems = vec![];
+    let mut in_zenhub: HashSet<IssueKey> = HashSet::new();
+    for (pipeline_id, pipeline_name) in pipelines {
+        info!(pipeline = %pipeline_name, "Getting issues for Zenhub pipeline");
+        let issues =
+            zenhub::get_pipeline_issues(zenhub_client.clone(), &pipeline_id, &workspace.id, None)?;
+        for PagedIssue {
+            issue: zh_issue, ..
+        } in issues
+        {
+            let issue = IssueKey {
+                owner: zh_issue.repository.owner.login.clone(),
+                repo: zh_issue.repository.name.clone(),
+                number: zh_issue.number,
+            };
+            in_zenhub.insert(issue.clone());
+            let closed =
+                pipeline_id == closed_pipeline_id || matches!(zh_issue.state, IssueState::CLOSED);
+            let item = project_items.get(&issue);
+            let expected_status = match (closed, config.closed.policy) {
+                (true, ClosedPolicy::Skip) => continue,
+                (true, ClosedPolicy::Archive) => {
+                    if let Some(item) = item.filter(|i| !i.archived) {
+                        problems.push(Problem::NotArchived {
+                            issue,
+                            item_id: item.item_id.clone(),
+                        });
+                    }
+                    continue;
+                }
+                (true, ClosedPolicy::Done) => config.closed.status.clone(),
+                (false, _) => config.lanes.get(&pipeline_name).cloned(),
+            };
+            let Some(item) = item else {
+                problems.push(Problem::MissingFromProject {
+                    issue,
+                    pipeline: pipeline_name.clone(),
+                });
+                continue;
+            };
+
+            let zh_priority = zh_issue.pipeline_issue.and_then(|p| p.priority);
+            let expected_priority = config
+                .priorities
+                .gh_option(
+                    zh_priority
+                        .as_ref()
+                        .map(|p| (p.id.as_str(), p.name.as_str())),
+                )
+                .cloned();
+            let expected_estimate = zh_issue.estimate.map(|e| e.value.to_string());
+            let checks = [
+                (
+                    &field_mapping["Pipeline"],
+                    &status_field_id,
+                    expected_status,
+                ),
+                (
+                    &field_mapping["Estimate"],
+                    &estimate_field_id,
+                    expected_estimate,
+                ),
+                (
+                    &field_mapping["Priority"],
+                    &priority_field_id,
+                    expected_priority,
+                ),
+            ];
+            for (field, field_id, expected) in checks {
+                let actual = actual_value(item, field_id, &option_names);
+                if actual != expected {
+                    problems.push(Problem::Mismatch {
+                        issue: issue.clone(),
+                        item_id: item.item_id.clone(),
+                        field: field.clone(),
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut extra: Vec<(&IssueKey, &ProjectItem)> = project_items
+        .iter()
+        .filter(|(issue, _)| !in_zenhub.contains(*issue))
+        .collect();
+    extra.sort_by_key(|(_, item)| item.position);
+    problems.extend(extra.into_iter().map(|(issue, item)| Problem::NotInZenhub {
+        issue: issue.clone(),
+        item_id: item.item_id.clone(),
+    }));
+
+    Ok(problems)
+}
+
+// option ID -> name for every single select field, so values can be compared by name
+fn option_names(gh_fields: &[ProjectField]) -> HashMap<&str, &str> {
+    gh_fields
+        .iter()
+        .filter_map(|f| match f {
+            ProjectField::ProjectV2SingleSelectField(ssf) => Some(&ssf.options),
+            _ => None,
+        })
+        .flatten()
+        .map(|o| (o.id.as_str(), o.name.as_str()))
+        .collect()
+}
+
+// the item's value for a field as it reads in GH, `None` when it's empty
+fn actual_value(
+    item: &ProjectItem,
+    field_id: &str,
+    option_names: &HashMap<&str, &str>,
+) -> Option<String> {
+    match item.field_values.get(field_id)? {
+        FieldValue::Number(v) => v.map(|v| v.to_string()),
+        FieldValue::SingleSelect(v) => v.as_deref().map(|id| {
+            option_names
+                .get(id)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| id.to_string())
+        }),
+        FieldValue::Text(v) | FieldValue::Iteration(v) => v.clone(),
+    }
+}
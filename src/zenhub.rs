@@ -1,38 +1,176 @@
-use graphql_client::{reqwest::post_graphql_blocking as post_graphql, GraphQLQuery};
+use anyhow::{anyhow, Error};
+use graphql_client::GraphQLQuery;
 use reqwest::blocking::Client;
+use serde::Serialize;
+use std::sync::OnceLock;
 
-pub const URL: &str = "https://api.zenhub.com/public/graphql";
+use crate::{config::WorkspaceRef, error::ApiError, request::post_graphql};
+
+const DEFAULT_URL: &str = "https://api.zenhub.com/public/graphql";
+static URL: OnceLock<String> = OnceLock::new();
+
+/// Point every ZH call at a different GraphQL endpoint, i.e. a Zenhub Enterprise on-prem install's.
+/// Only the first call has any effect, so set it before making any requests.
+pub fn set_url(url: String) {
+    let _ = URL.set(url);
+}
+
+fn url() -> &'static str {
+    URL.get().map(String::as_str).unwrap_or(DEFAULT_URL)
+}
+
+type ISO8601DateTime = String;
+// ZH sends these as strings since they don't fit in 32 bits
+type BigInt = String;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "schemas/zenhub.graphql",
     query_path = "queries/zenhub/get_workspace.graphql",
-    response_derives = "Debug, Clone"
+    response_derives = "Debug, Clone, Serialize"
 )]
 pub struct GetWorkspace;
 
-pub fn get_workspace(
-    client: Client,
-    name: &str,
-) -> Result<get_workspace::GetWorkspaceRecentlyViewedWorkspacesNodes, anyhow::Error> {
+pub type Workspace = get_workspace::GetWorkspaceWorkspace;
+
+pub fn get_workspace_by_id(client: Client, id: &str) -> Result<Workspace, ApiError> {
     use get_workspace::*;
-    let response_body = post_graphql::<GetWorkspace, _>(&client, URL, Variables {})?;
-    let response_data: ResponseData = response_body
-        .data
-        .expect("Failed to get Zenhub workspace data.");
-    let workspaces = response_data.recently_viewed_workspaces.nodes;
-    let desired_workspace = workspaces
-        .iter()
-        .find(|w| w.name == Some(name.to_string()))
-        .expect(&format!(
-            "No name matching {name} found in response: {:?}",
-            workspaces
-                .iter()
-                .map(|w| w.name.clone())
-                .collect::<Vec<Option<String>>>()
-        ));
+    let response_data: ResponseData =
+        post_graphql::<GetWorkspace, _>(&client, url(), Variables { id: id.to_string() })?;
+    response_data.workspace.ok_or_else(|| {
+        ApiError::not_found(&format!(
+            "No ZH workspace with ID {id}, or the token can't see it."
+        ))
+    })
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/zenhub.graphql",
+    query_path = "queries/zenhub/find_organizations.graphql",
+    response_derives = "Debug, Clone"
+)]
+pub struct FindOrganizations;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/zenhub.graphql",
+    query_path = "queries/zenhub/find_workspaces.graphql",
+    response_derives = "Debug, Clone, Serialize"
+)]
+pub struct FindWorkspaces;
+
+/// One of the token's ZH organizations, along with its workspaces.
+#[derive(Debug, Clone, Serialize)]
+pub struct Organization {
+    pub id: String,
+    pub name: Option<String>,
+    pub workspaces: Vec<find_workspaces::FindWorkspacesNodeOnZenhubOrganizationWorkspacesNodes>,
+}
+
+/// The token's organizations and their workspaces, only the workspaces matching `query` if it's set.
+pub fn get_workspaces(client: Client, query: Option<&str>) -> Result<Vec<Organization>, ApiError> {
+    let mut organizations = vec![];
+    let mut has_next_page = true;
+    let mut end_cursor = None;
+    while has_next_page {
+        let variables = find_organizations::Variables {
+            end_cursor: end_cursor.clone(),
+        };
+        let response_data = post_graphql::<FindOrganizations, _>(&client, url(), variables)?
+            .viewer
+            .zenhub_organizations;
+        for organization in response_data.nodes {
+            organizations.push(Organization {
+                workspaces: get_organization_workspaces(client.clone(), &organization.id, query)?,
+                id: organization.id,
+                name: organization.name,
+            });
+        }
+        has_next_page = response_data.page_info.has_next_page;
+        end_cursor = response_data.page_info.end_cursor;
+    }
 
-    Ok(desired_workspace.clone())
+    Ok(organizations)
+}
+
+fn get_organization_workspaces(
+    client: Client,
+    organization_id: &str,
+    query: Option<&str>,
+) -> Result<Vec<find_workspaces::FindWorkspacesNodeOnZenhubOrganizationWorkspacesNodes>, ApiError> {
+    use find_workspaces::*;
+
+    let mut workspaces = vec![];
+    let mut has_next_page = true;
+    let mut end_cursor = None;
+    while has_next_page {
+        let variables = Variables {
+            organization_id: organization_id.to_string(),
+            query: query.map(|q| q.to_string()),
+            end_cursor: end_cursor.clone(),
+        };
+        let response_data = match post_graphql::<FindWorkspaces, _>(&client, url(), variables)?
+            .node
+            .ok_or_else(|| ApiError::not_found("No ZH organization found for its workspaces."))?
+        {
+            FindWorkspacesNode::ZenhubOrganization(organization) => organization.workspaces,
+            _ => {
+                return Err(ApiError::missing(
+                    "Recieved a non-organization node back from the ZH workspaces request.",
+                ))
+            }
+        };
+        workspaces.extend(response_data.nodes);
+        has_next_page = response_data.page_info.has_next_page;
+        end_cursor = response_data.page_info.end_cursor;
+    }
+
+    Ok(workspaces)
+}
+
+/// Look the workspace up directly by ID, or search the token's organizations for it by name. Either
+/// way the token doesn't need to have opened the workspace recently.
+pub fn get_workspace(client: Client, workspace: &WorkspaceRef) -> Result<Workspace, Error> {
+    let id = match workspace {
+        WorkspaceRef::Id(id) => id.clone(),
+        WorkspaceRef::Name(name) => {
+            let organizations = get_workspaces(client.clone(), Some(name))?;
+            // the search is fuzzy, so only exact names count
+            let matches: Vec<(Option<&str>, &str)> = organizations
+                .iter()
+                .flat_map(|o| {
+                    o.workspaces
+                        .iter()
+                        .filter(|w| w.name.as_deref() == Some(name.as_str()))
+                        .map(|w| (o.name.as_deref(), w.id.as_str()))
+                })
+                .collect();
+            match matches.as_slice() {
+                [(_, id)] => id.to_string(),
+                [] => {
+                    return Err(anyhow!(
+                        "No ZH workspace named {name} in the token's organizations. Close matches: {:?}",
+                        organizations
+                            .iter()
+                            .flat_map(|o| o.workspaces.iter().filter_map(|w| w.name.clone()))
+                            .collect::<Vec<String>>()
+                    ))
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "More than one ZH workspace is named {name}, use its ID or URL instead: {}",
+                        matches
+                            .iter()
+                            .map(|(org, id)| format!("{id} in {}", org.unwrap_or("(unnamed)")))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ))
+                }
+            }
+        }
+    };
+    Ok(get_workspace_by_id(client, &id)?)
 }
 
 #[derive(GraphQLQuery)]
@@ -43,38 +181,94 @@ pub fn get_workspace(
 )]
 pub struct GetPipelineIssues;
 
+/// A pipeline issue along with the page it was read from, so reading can be picked back up there.
+#[derive(Debug, Clone)]
+pub struct PagedIssue {
+    // index of the page within this read, only comparable between issues from the same read
+    pub page: usize,
+    // the cursor the page was requested with, `None` for the start of the pipeline
+    pub cursor: Option<String>,
+    pub issue: get_pipeline_issues::GetPipelineIssuesSearchIssuesByPipelineNodes,
+}
+
+// `start_cursor` skips the pages before it, pass `None` to read the whole pipeline
 pub fn get_pipeline_issues(
     client: Client,
     pipeline_id: &str,
     workspace_id: &str,
-) -> Result<Vec<get_pipeline_issues::GetPipelineIssuesSearchIssuesByPipelineNodes>, anyhow::Error> {
+    start_cursor: Option<String>,
+) -> Result<Vec<PagedIssue>, ApiError> {
     use get_pipeline_issues::*;
 
     let mut pipeline_issues = vec![];
     let mut has_next_page = true;
-    let mut end_cursor = None;
+    let mut end_cursor = start_cursor;
+    let mut page = 0;
     while has_next_page {
         let variables = Variables {
             pipeline_id: pipeline_id.to_string(),
             workspace_id: workspace_id.to_string(),
             end_cursor: end_cursor.clone(),
         };
-        let response_body = post_graphql::<GetPipelineIssues, _>(&client, URL, variables)?;
-        if response_body.errors.is_some() {
-            println!(
-                "Error while getting ZH Pipeline issues {:?}",
-                response_body.errors.as_ref().unwrap()
-            );
-        }
-        let response_data = response_body
-            .data
-            .expect("Failed to get Zenhub pipeline issue data.")
+        let response_data = post_graphql::<GetPipelineIssues, _>(&client, url(), variables)?
             .search_issues_by_pipeline
-            .expect("No issue data recieved for pipeline.");
+            .ok_or_else(|| ApiError::missing("No issue data recieved for pipeline."))?;
+        pipeline_issues.extend(response_data.nodes.into_iter().map(|issue| PagedIssue {
+            page,
+            cursor: end_cursor.clone(),
+            issue,
+        }));
         has_next_page = response_data.page_info.has_next_page;
         end_cursor = response_data.page_info.end_cursor;
-        pipeline_issues.append(&mut response_data.nodes.clone());
+        page += 1;
     }
+    // top of the pipeline first, issues without a position keep the order ZH returned them in
+    pipeline_issues.sort_by_key(|paged| {
+        paged
+            .issue
+            .pipeline_issue
+            .as_ref()
+            .and_then(|p| p.relative_position.parse::<i128>().ok())
+            .unwrap_or(i128::MAX)
+    });
 
     Ok(pipeline_issues)
 }
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schemas/zenhub.graphql",
+    query_path = "queries/zenhub/get_workspace_repositories.graphql",
+    response_derives = "Debug, Clone"
+)]
+pub struct GetWorkspaceRepositories;
+
+pub fn get_workspace_repositories(
+    client: Client,
+    workspace_id: &str,
+) -> Result<
+    Vec<get_workspace_repositories::GetWorkspaceRepositoriesWorkspaceRepositoriesConnectionNodes>,
+    ApiError,
+> {
+    use get_workspace_repositories::*;
+
+    let mut repositories = vec![];
+    let mut has_next_page = true;
+    let mut end_cursor = None;
+    while has_next_page {
+        let variables = Variables {
+            workspace_id: workspace_id.to_string(),
+            end_cursor: end_cursor.clone(),
+        };
+        let response_data = post_graphql::<GetWorkspaceRepositories, _>(&client, url(), variables)?
+            .workspace
+            .ok_or_else(|| ApiError::not_found("No ZH workspace found for its repositories."))?
+            .repositories_connection
+            .ok_or_else(|| ApiError::missing("No repository data recieved for the workspace."))?;
+        repositories.extend(response_data.nodes);
+        has_next_page = response_data.page_info.has_next_page;
+        end_cursor = response_data.page_info.end_cursor;
+    }
+
+    Ok(repositories)
+}